@@ -0,0 +1,168 @@
+extern crate gl;
+use crate::framebuffer::{self, ColourFormat, Framebuffer};
+use crate::shader::{self, Shader};
+use gl::types::*;
+
+pub struct Settings {
+    enabled: bool,
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+}
+
+impl Settings {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        println!("Bloom: {}", if self.enabled { "on" } else { "off" });
+    }
+
+    pub fn adjust_threshold(&mut self, delta: f32) {
+        self.threshold = (self.threshold + delta).max(0.0);
+        println!("Bloom threshold: {:.2}", self.threshold);
+    }
+
+    pub fn scale_intensity(&mut self, factor: f32) {
+        self.intensity = num::clamp(self.intensity * factor, 0.01, 10.0);
+        println!("Bloom intensity: {:.2}", self.intensity);
+    }
+}
+
+pub fn settings(threshold: f32, intensity: f32) -> Settings {
+    Settings {
+        enabled: true,
+        threshold,
+        knee: 0.5,
+        intensity,
+    }
+}
+
+pub struct Bloom {
+    levels: Vec<Framebuffer>,
+    threshold: Shader,
+    downsample: Shader,
+    upsample: Shader,
+    vao: GLuint,
+}
+
+impl Bloom {
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.levels = create_levels(width, height, self.levels.len())?;
+        Ok(())
+    }
+
+    pub fn apply(&self, scene: &Framebuffer, settings: &Settings) -> Result<(), String> {
+        if !settings.enabled || self.levels.is_empty() {
+            return Ok(());
+        }
+
+        // The caller's vertex array is bound again afterwards
+        let mut caller_vao: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut caller_vao);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        // Bright pass
+        self.levels[0].bind()?;
+        scene.bind_colour_texture();
+        self.threshold.enable();
+        self.threshold.set_int("uImage", 0)?;
+        self.threshold.set_float("uThreshold", settings.threshold)?;
+        self.threshold.set_float("uKnee", settings.knee)?;
+        draw_fullscreen();
+
+        // Downsample chain
+        self.downsample.enable();
+        self.downsample.set_int("uImage", 0)?;
+        for pair in self.levels.windows(2) {
+            pair[1].bind()?;
+            pair[0].bind_colour_texture();
+            draw_fullscreen();
+        }
+
+        // Upsample chain, accumulating into each larger level
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+        }
+        self.upsample.enable();
+        self.upsample.set_int("uImage", 0)?;
+        self.upsample.set_float("uIntensity", 1.0)?;
+        for pair in self.levels.windows(2).rev() {
+            pair[0].bind()?;
+            pair[1].bind_colour_texture();
+            draw_fullscreen();
+        }
+
+        // Composite back onto the HDR scene
+        scene.bind()?;
+        self.levels[0].bind_colour_texture();
+        self.upsample.set_float("uIntensity", settings.intensity)?;
+        draw_fullscreen();
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::BindVertexArray(caller_vao as GLuint);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Bloom {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+pub fn create(width: u32, height: u32, level_count: usize) -> Result<Bloom, String> {
+    let threshold = compile(include_str!("bloom_threshold.frag"))?;
+    let downsample = compile(include_str!("bloom_downsample.frag"))?;
+    let upsample = compile(include_str!("bloom_upsample.frag"))?;
+
+    let levels = create_levels(width, height, level_count)?;
+
+    let mut vao: GLuint = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+
+    Ok(Bloom {
+        levels,
+        threshold,
+        downsample,
+        upsample,
+        vao,
+    })
+}
+
+// Each level is half the size of the one before, starting at half resolution
+fn create_levels(width: u32, height: u32, level_count: usize) -> Result<Vec<Framebuffer>, String> {
+    let mut levels = Vec::with_capacity(level_count);
+    let (mut level_width, mut level_height) = (width, height);
+    for _ in 0..level_count {
+        level_width = (level_width / 2).max(1);
+        level_height = (level_height / 2).max(1);
+        levels.push(framebuffer::create(
+            level_width,
+            level_height,
+            ColourFormat::Rgba16F,
+            false,
+        )?);
+    }
+    Ok(levels)
+}
+
+fn compile(fragment_source: &str) -> Result<Shader, String> {
+    shader::compile_from_sources(include_str!("fullscreen.vert"), fragment_source)
+}
+
+fn draw_fullscreen() {
+    unsafe {
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+}
@@ -0,0 +1,20 @@
+#version 330 core
+
+uniform sampler2D uImage;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    vec2 half_texel = 0.5 / vec2(textureSize(uImage, 0));
+
+    vec3 sum = texture(uImage, aTextureCoords).rgb * 4.0;
+    sum += texture(uImage, aTextureCoords - half_texel).rgb;
+    sum += texture(uImage, aTextureCoords + half_texel).rgb;
+    sum += texture(uImage, aTextureCoords + vec2(half_texel.x, -half_texel.y)).rgb;
+    sum += texture(uImage, aTextureCoords - vec2(half_texel.x, -half_texel.y)).rgb;
+
+    aFragColours = vec4(sum / 8.0, 1.0);
+}
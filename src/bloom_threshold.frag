@@ -0,0 +1,21 @@
+#version 330 core
+
+uniform sampler2D uImage;
+uniform float uThreshold;
+uniform float uKnee;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    vec3 colour = texture(uImage, aTextureCoords).rgb;
+    float brightness = max(colour.r, max(colour.g, colour.b));
+
+    float soft = clamp(brightness - uThreshold + uKnee, 0.0, 2.0 * uKnee);
+    soft = soft * soft / (4.0 * uKnee + 0.00001);
+    float contribution = max(soft, brightness - uThreshold) / max(brightness, 0.00001);
+
+    aFragColours = vec4(colour * contribution, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 330 core
+
+uniform sampler2D uImage;
+uniform float uIntensity;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    vec2 half_texel = 0.5 / vec2(textureSize(uImage, 0));
+
+    vec3 sum = texture(uImage, aTextureCoords + vec2(-half_texel.x * 2.0, 0.0)).rgb;
+    sum += texture(uImage, aTextureCoords + vec2(-half_texel.x, half_texel.y)).rgb * 2.0;
+    sum += texture(uImage, aTextureCoords + vec2(0.0, half_texel.y * 2.0)).rgb;
+    sum += texture(uImage, aTextureCoords + vec2(half_texel.x, half_texel.y)).rgb * 2.0;
+    sum += texture(uImage, aTextureCoords + vec2(half_texel.x * 2.0, 0.0)).rgb;
+    sum += texture(uImage, aTextureCoords + vec2(half_texel.x, -half_texel.y)).rgb * 2.0;
+    sum += texture(uImage, aTextureCoords + vec2(0.0, -half_texel.y * 2.0)).rgb;
+    sum += texture(uImage, aTextureCoords + vec2(-half_texel.x, -half_texel.y)).rgb * 2.0;
+
+    aFragColours = vec4(sum / 12.0 * uIntensity, 1.0);
+}
@@ -0,0 +1,54 @@
+use nalgebra_glm as glm;
+
+// Axis aligned
+#[derive(Copy, Clone)]
+pub struct Bounds {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Bounds {
+    pub fn centre(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    // The box around all eight transformed corners, so it can only grow
+    pub fn transform(&self, matrix: &glm::Mat4) -> Bounds {
+        let corners = (0..8).map(|i| {
+            let corner = glm::vec3(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            glm::vec4_to_vec3(&(matrix * glm::vec4(corner.x, corner.y, corner.z, 1.0)))
+        });
+        // Eight corners are never empty
+        from_points(corners).unwrap_or(*self)
+    }
+}
+
+pub fn from_points(points: impl IntoIterator<Item = glm::Vec3>) -> Option<Bounds> {
+    points.into_iter().fold(None, |bounds, point| {
+        Some(match bounds {
+            Some(Bounds { min, max }) => Bounds {
+                min: glm::min2(&min, &point),
+                max: glm::max2(&max, &point),
+            },
+            None => Bounds {
+                min: point,
+                max: point,
+            },
+        })
+    })
+}
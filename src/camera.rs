@@ -1,3 +1,4 @@
+use crate::frustum::{self, Frustum};
 use nalgebra_glm as glm;
 
 #[derive(Copy, Clone)]
@@ -10,6 +11,104 @@ pub enum Direction {
     Down,
 }
 
+// Angles are in radians, distances in world units
+#[derive(Copy, Clone)]
+pub enum Projection {
+    Perspective { y_fov: f32, near: f32, far: f32 },
+    // `height` is the visible extent vertically, the width follows the aspect ratio
+    Orthographic { height: f32, near: f32, far: f32 },
+    // Depth runs from 1 at the near plane to 0 at infinity, so it needs a
+    // GREATER depth test and a depth buffer cleared to 0
+    ReversedInfinite { y_fov: f32, near: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> glm::Mat4 {
+        match *self {
+            Projection::Perspective { y_fov, near, far } => {
+                glm::perspective(aspect, y_fov, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let (half_width, half_height) = (height * aspect / 2.0, height / 2.0);
+                glm::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+            Projection::ReversedInfinite { y_fov, near } => {
+                glm::reversed_infinite_perspective_rh_zo(aspect, y_fov, near)
+            }
+        }
+    }
+
+    // An ordinary projection of the same shape limited to `near..far`, for
+    // fitting things like shadow cascades to part of the view
+    pub fn clipped(&self, aspect: f32, near: f32, far: f32) -> glm::Mat4 {
+        match *self {
+            Projection::Perspective { y_fov, .. } | Projection::ReversedInfinite { y_fov, .. } => {
+                glm::perspective(aspect, y_fov, near, far)
+            }
+            Projection::Orthographic { height, .. } => {
+                Projection::Orthographic { height, near, far }.matrix(aspect)
+            }
+        }
+    }
+
+    pub fn depth_range(&self) -> (f32, f32) {
+        match *self {
+            Projection::Perspective { near, far, .. }
+            | Projection::Orthographic { near, far, .. } => (near, far),
+            Projection::ReversedInfinite { near, .. } => (near, f32::INFINITY),
+        }
+    }
+
+    pub fn is_reversed_z(&self) -> bool {
+        matches!(self, Projection::ReversedInfinite { .. })
+    }
+
+    // The same kind of projection and depth range with a new field of view;
+    // orthographic projections become perspective ones
+    pub fn with_y_fov(&self, y_fov: f32) -> Projection {
+        match *self {
+            Projection::Perspective { near, far, .. }
+            | Projection::Orthographic { near, far, .. } => {
+                Projection::Perspective { y_fov, near, far }
+            }
+            Projection::ReversedInfinite { near, .. } => {
+                Projection::ReversedInfinite { y_fov, near }
+            }
+        }
+    }
+
+    // What the toggle switches to: an orthographic view framing roughly what
+    // the perspective one shows at ORTHOGRAPHIC_DISTANCE, or back again
+    fn alternate(&self) -> Projection {
+        match *self {
+            Projection::Perspective { y_fov, near, far } => Projection::Orthographic {
+                height: 2.0 * ORTHOGRAPHIC_DISTANCE * (y_fov / 2.0).tan(),
+                near,
+                far,
+            },
+            Projection::ReversedInfinite { y_fov, near } => Projection::Orthographic {
+                height: 2.0 * ORTHOGRAPHIC_DISTANCE * (y_fov / 2.0).tan(),
+                near,
+                far: ORTHOGRAPHIC_DISTANCE * 10.0,
+            },
+            Projection::Orthographic { height, near, far } => Projection::Perspective {
+                y_fov: 2.0 * (height / 2.0 / ORTHOGRAPHIC_DISTANCE).atan(),
+                near,
+                far,
+            },
+        }
+    }
+}
+
+const ORTHOGRAPHIC_DISTANCE: f32 = 10.0;
+
 pub struct Camera {
     position: glm::Vec3,
     front: glm::Vec3,
@@ -17,6 +116,13 @@ pub struct Camera {
     up: glm::Vec3,
     yaw: f32,
     pitch: f32,
+    projection: Projection,
+    // Swapped with `projection` when toggling
+    alternate_projection: Projection,
+    aspect: f32,
+    // Some in free mode, where the camera turns about its own axes and can roll;
+    // yaw and pitch are only kept up to date in the Euler mode
+    orientation: Option<glm::Quat>,
 }
 
 impl Camera {
@@ -24,6 +130,42 @@ impl Camera {
         glm::look_at(&self.position, &(self.position + self.front), &self.up)
     }
 
+    pub fn get_projection_matrix(&self) -> glm::Mat4 {
+        self.projection.matrix(self.aspect)
+    }
+
+    pub fn get_view_projection(&self) -> glm::Mat4 {
+        self.get_projection_matrix() * self.get_view_matrix()
+    }
+
+    // In world space
+    pub fn get_frustum(&self) -> Frustum {
+        frustum::from_matrix(&self.get_view_projection(), self.projection.is_reversed_z())
+    }
+
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn get_aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+    }
+
+    // Perspective projections have no height and are left alone
+    pub fn set_orthographic_height(&mut self, height: f32) {
+        if let Projection::Orthographic { near, far, .. } = self.projection {
+            self.projection = Projection::Orthographic { height, near, far };
+        }
+    }
+
+    pub fn toggle_projection(&mut self) {
+        std::mem::swap(&mut self.projection, &mut self.alternate_projection);
+    }
+
     pub fn get_position(self: &Self) -> glm::Vec3 {
         self.position
     }
@@ -32,18 +174,112 @@ impl Camera {
         self.front
     }
 
-    pub fn update_position(self: &mut Self, direction: Direction, velocity: f32) {
+    pub fn get_right(&self) -> glm::Vec3 {
+        self.right
+    }
+
+    pub fn get_up(&self) -> glm::Vec3 {
+        self.up
+    }
+
+    // Yaw and pitch in degrees; roll is lost
+    pub fn get_angles(&self) -> (f32, f32) {
+        match self.orientation {
+            Some(_) => angles_from_front(&self.front),
+            None => (self.yaw, self.pitch),
+        }
+    }
+
+    // Levels out any roll
+    pub fn place(&mut self, position: glm::Vec3, yaw: f32, pitch: f32) {
+        self.position = position;
+        (self.yaw, self.pitch) = (yaw, num::clamp(pitch, -89.0, 89.0));
+        (self.front, self.right, self.up) =
+            calculate_camera_vectors(&glm::vec3(0.0, 1.0, 0.0), self.yaw, self.pitch);
+        if self.orientation.is_some() {
+            self.orientation = Some(orientation_from_vectors(&self.front, &self.up));
+        }
+    }
+
+    pub fn get_orientation(&self) -> glm::Quat {
+        self.orientation
+            .unwrap_or_else(|| orientation_from_vectors(&self.front, &self.up))
+    }
+
+    // The Euler mode can't roll, so it keeps only the view direction
+    pub fn set_pose(&mut self, position: glm::Vec3, orientation: glm::Quat) {
+        self.position = position;
+        match self.orientation {
+            Some(_) => self.set_orientation(orientation),
+            None => {
+                let front = glm::quat_rotate_vec3(&orientation, &glm::vec3(0.0, 0.0, -1.0));
+                let (yaw, pitch) = angles_from_front(&front);
+                self.place(position, yaw, pitch);
+            }
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.orientation.is_some()
+    }
+
+    // Switches between free and Euler mode without moving the view, apart from
+    // levelling out roll when returning to Euler mode
+    pub fn toggle_free(&mut self) {
+        match self.orientation {
+            Some(_) => {
+                self.orientation = None;
+                let (yaw, pitch) = angles_from_front(&self.front);
+                self.place(self.position, yaw, pitch);
+            }
+            None => self.orientation = Some(orientation_from_vectors(&self.front, &self.up)),
+        }
+    }
+
+    // Degrees clockwise about the view direction, only in free mode
+    pub fn roll(&mut self, angle: f32) {
+        if let Some(orientation) = self.orientation {
+            self.set_orientation(
+                orientation * glm::quat_angle_axis(radians(angle), &glm::vec3(0.0, 0.0, -1.0)),
+            );
+        }
+    }
+
+    fn set_orientation(&mut self, orientation: glm::Quat) {
+        let orientation = glm::quat_normalize(&orientation);
+        self.front = glm::quat_rotate_vec3(&orientation, &glm::vec3(0.0, 0.0, -1.0));
+        self.right = glm::quat_rotate_vec3(&orientation, &glm::vec3(1.0, 0.0, 0.0));
+        self.up = glm::quat_rotate_vec3(&orientation, &glm::vec3(0.0, 1.0, 0.0));
+        self.orientation = Some(orientation);
+    }
+
+    // Unit vector in world space
+    pub fn get_direction(&self, direction: Direction) -> glm::Vec3 {
         match direction {
-            Direction::Forward => self.position += velocity * self.front,
-            Direction::Backward => self.position -= velocity * self.front,
-            Direction::Left => self.position -= velocity * self.right,
-            Direction::Right => self.position += velocity * self.right,
-            Direction::Up => self.position += velocity * self.up,
-            Direction::Down => self.position -= velocity * self.up,
+            Direction::Forward => self.front,
+            Direction::Backward => -self.front,
+            Direction::Left => -self.right,
+            Direction::Right => self.right,
+            Direction::Up => self.up,
+            Direction::Down => -self.up,
         }
     }
 
+    pub fn translate(&mut self, offset: &glm::Vec3) {
+        self.position += offset;
+    }
+
     pub fn update_orientation(self: &mut Self, x_offset: f32, y_offset: f32, sensitivity: f32) {
+        // Free mode turns about the camera's own up and right axes, with no limits
+        if let Some(orientation) = self.orientation {
+            let yaw =
+                glm::quat_angle_axis(radians(-sensitivity * x_offset), &glm::vec3(0.0, 1.0, 0.0));
+            let pitch =
+                glm::quat_angle_axis(radians(sensitivity * y_offset), &glm::vec3(1.0, 0.0, 0.0));
+            self.set_orientation(orientation * yaw * pitch);
+            return;
+        }
+
         (self.pitch, self.yaw) =
             modify_pitch_and_yaw(self.pitch, self.yaw, x_offset, y_offset, sensitivity);
 
@@ -52,9 +288,27 @@ impl Camera {
     }
 }
 
-pub fn start_from_world_pos(position: glm::Vec3) -> Camera {
-    let yaw = -90.0;
-    let pitch = 0.0;
+pub fn look_from(position: glm::Vec3, front: &glm::Vec3, projection: Projection) -> Camera {
+    let (yaw, pitch) = angles_from_front(front);
+    create(position, yaw, pitch, projection)
+}
+
+fn angles_from_front(front: &glm::Vec3) -> (f32, f32) {
+    let front = glm::normalize(front);
+    let yaw = num::Float::to_degrees(front.z.atan2(front.x));
+    let pitch = num::clamp(num::Float::to_degrees(front.y.asin()), -89.0, 89.0);
+    (yaw, pitch)
+}
+
+// The rotation taking the camera's local axes (looking down -Z) onto these vectors
+fn orientation_from_vectors(front: &glm::Vec3, up: &glm::Vec3) -> glm::Quat {
+    let right = glm::normalize(&glm::cross(front, up));
+    let up = glm::cross(&right, front);
+    glm::mat3_to_quat(&glm::Mat3::from_columns(&[right, up, -front]))
+}
+
+// The aspect ratio starts square, callers set it from the window
+pub fn create(position: glm::Vec3, yaw: f32, pitch: f32, projection: Projection) -> Camera {
     let (front, right, up) = calculate_camera_vectors(&glm::vec3(0.0, 1.0, 0.0), yaw, pitch);
     Camera {
         position,
@@ -63,9 +317,17 @@ pub fn start_from_world_pos(position: glm::Vec3) -> Camera {
         up,
         yaw,
         pitch,
+        projection,
+        alternate_projection: projection.alternate(),
+        aspect: 1.0,
+        orientation: None,
     }
 }
 
+pub fn front_from_angles(yaw: f32, pitch: f32) -> glm::Vec3 {
+    calculate_camera_vectors(&glm::vec3(0.0, 1.0, 0.0), yaw, pitch).0
+}
+
 fn radians(value: f32) -> f32 {
     num::Float::to_radians(value)
 }
@@ -0,0 +1,294 @@
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Seconds between keyframes recorded interactively
+const KEYFRAME_INTERVAL: f32 = 2.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    // Seconds from the start of the path, increasing along it
+    pub time: f32,
+    pub position: [f32; 3],
+    // x, y, z, w, taking the camera's local -Z onto its view direction
+    pub orientation: [f32; 4],
+}
+
+impl Keyframe {
+    fn position(&self) -> glm::Vec3 {
+        glm::Vec3::from(self.position)
+    }
+
+    fn orientation(&self) -> glm::Quat {
+        let [x, y, z, w] = self.orientation;
+        glm::quat(x, y, z, w)
+    }
+}
+
+// Positions follow a Catmull-Rom spline through the keyframes, orientations
+// are slerped between neighbouring keyframes
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn add(&mut self, position: glm::Vec3, orientation: glm::Quat) {
+        let time = match self.keyframes.last() {
+            Some(last) => last.time + KEYFRAME_INTERVAL,
+            None => 0.0,
+        };
+        self.keyframes.push(Keyframe {
+            time,
+            position: position.into(),
+            orientation: [orientation.i, orientation.j, orientation.k, orientation.w],
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.keyframes.len()
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time)
+    }
+
+    // Clamped to the ends of the path, None when it has no keyframes
+    pub fn sample(&self, time: f32) -> Option<(glm::Vec3, glm::Quat)> {
+        let keys = &self.keyframes;
+        let last = keys.len().checked_sub(1)?;
+        let i = keys[..last]
+            .iter()
+            .rposition(|key| key.time <= time)
+            .unwrap_or(0);
+        if i == last {
+            return Some((keys[i].position(), keys[i].orientation()));
+        }
+
+        let (start, end) = (&keys[i], &keys[i + 1]);
+        let span = end.time - start.time;
+        let u = if span > 0.0 {
+            num::clamp((time - start.time) / span, 0.0, 1.0)
+        } else {
+            1.0
+        };
+        // The ends reuse their own keyframe as the missing neighbour
+        let before = &keys[i.saturating_sub(1)];
+        let after = &keys[(i + 2).min(last)];
+        let position = catmull_rom(
+            &before.position(),
+            &start.position(),
+            &end.position(),
+            &after.position(),
+            u,
+        );
+        let orientation = glm::quat_slerp(&start.orientation(), &end.orientation(), u);
+        Some((position, orientation))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(error_to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+pub struct Playback {
+    time: f32,
+    looping: bool,
+}
+
+impl Playback {
+    // The pose after moving on `delta_seconds`, None once a non-looping playback
+    // has passed the end
+    pub fn advance(
+        &mut self,
+        path: &CameraPath,
+        delta_seconds: f32,
+    ) -> Option<(glm::Vec3, glm::Quat)> {
+        self.time += delta_seconds;
+        let duration = path.duration();
+        if self.time > duration {
+            if !self.looping {
+                return None;
+            }
+            self.time = if duration > 0.0 {
+                self.time % duration
+            } else {
+                0.0
+            };
+        }
+        path.sample(self.time)
+    }
+}
+
+pub fn play(looping: bool) -> Playback {
+    Playback { time: 0.0, looping }
+}
+
+pub fn create() -> CameraPath {
+    CameraPath::default()
+}
+
+pub fn load(path: &Path) -> Result<CameraPath, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse(&text, &path.display().to_string())
+}
+
+fn parse(text: &str, origin: &str) -> Result<CameraPath, String> {
+    let camera_path: CameraPath = ron::from_str(text).map_err(|e| format!("{}:{}", origin, e))?;
+    if camera_path
+        .keyframes
+        .windows(2)
+        .any(|pair| pair[1].time < pair[0].time)
+    {
+        return Err(format!("{}: keyframe times must not decrease", origin));
+    }
+    Ok(camera_path)
+}
+
+fn catmull_rom(
+    p0: &glm::Vec3,
+    p1: &glm::Vec3,
+    p2: &glm::Vec3,
+    p3: &glm::Vec3,
+    u: f32,
+) -> glm::Vec3 {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    (p1 * 2.0
+        + (p2 - p0) * u
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * u2
+        + (-p0 + p1 * 3.0 - p2 * 3.0 + p3) * u3)
+        * 0.5
+}
+
+fn error_to_string<E>() -> fn(E) -> String
+where
+    E: std::fmt::Display,
+{
+    |e: E| e.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: &glm::Vec3, expected: &glm::Vec3) {
+        assert!(
+            (actual - expected).norm() < 1e-5,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    fn turn(degrees: f32) -> glm::Quat {
+        glm::quat_angle_axis(degrees.to_radians(), &glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    // Keyframes 2 seconds apart along a bend, each turned 30 degrees further
+    fn bend() -> CameraPath {
+        let mut path = create();
+        for (i, position) in [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(4.0, 0.0, 0.0),
+            glm::vec3(4.0, 0.0, 4.0),
+            glm::vec3(8.0, 2.0, 4.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            path.add(position, turn(30.0 * i as f32));
+        }
+        path
+    }
+
+    #[test]
+    fn samples_pass_through_the_keyframes() {
+        let path = bend();
+        assert_eq!(path.duration(), 6.0);
+        for key in &path.keyframes {
+            let (position, orientation) = path.sample(key.time).unwrap();
+            assert_near(&position, &key.position());
+            assert!(glm::quat_dot(&orientation, &key.orientation()).abs() > 1.0 - 1e-5);
+        }
+    }
+
+    #[test]
+    fn samples_between_keyframes_follow_the_spline() {
+        let path = bend();
+        let keys: Vec<glm::Vec3> = path.keyframes.iter().map(Keyframe::position).collect();
+        // Halfway along a Catmull-Rom segment is (-p0 + 9 p1 + 9 p2 - p3) / 16
+        let (position, orientation) = path.sample(3.0).unwrap();
+        assert_near(
+            &position,
+            &((-keys[0] + keys[1] * 9.0 + keys[2] * 9.0 - keys[3]) / 16.0),
+        );
+        assert!(glm::quat_dot(&orientation, &turn(45.0)).abs() > 1.0 - 1e-5);
+
+        // The first segment has no keyframe before it, so the first is reused
+        let (position, _) = path.sample(1.0).unwrap();
+        assert_near(
+            &position,
+            &((-keys[0] + keys[0] * 9.0 + keys[1] * 9.0 - keys[2]) / 16.0),
+        );
+    }
+
+    #[test]
+    fn samples_are_clamped_to_the_ends() {
+        let path = bend();
+        assert_near(&path.sample(-1.0).unwrap().0, &glm::vec3(0.0, 0.0, 0.0));
+        assert_near(&path.sample(10.0).unwrap().0, &glm::vec3(8.0, 2.0, 4.0));
+        assert!(create().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn looping_playback_wraps_around() {
+        let path = bend();
+        let mut playback = play(true);
+        playback.advance(&path, 5.0);
+        let (position, _) = playback.advance(&path, 2.0).unwrap();
+        assert_near(&position, &path.sample(1.0).unwrap().0);
+
+        let mut playback = play(false);
+        assert!(playback.advance(&path, 5.0).is_some());
+        assert!(playback.advance(&path, 2.0).is_none());
+    }
+
+    #[test]
+    fn saved_paths_load_back() {
+        let path = bend();
+        let text = ron::ser::to_string(&path).unwrap();
+        let loaded = parse(&text, "path.ron").unwrap();
+        assert_eq!(loaded.len(), path.len());
+        assert_near(&loaded.sample(3.0).unwrap().0, &path.sample(3.0).unwrap().0);
+    }
+
+    #[test]
+    fn decreasing_times_are_rejected() {
+        let keyframe = |time: f32| {
+            format!(
+                "(time: {:?}, position: (0.0, 0.0, 0.0), orientation: (0.0, 0.0, 0.0, 1.0))",
+                time
+            )
+        };
+        let text = |times: &[f32]| {
+            let keyframes: Vec<String> = times.iter().map(|time| keyframe(*time)).collect();
+            format!("(keyframes: [{}])", keyframes.join(", "))
+        };
+        // Repeated times are allowed, the pose jumps there
+        assert!(parse(&text(&[0.0, 2.0, 2.0, 3.0]), "path.ron").is_ok());
+        assert_eq!(
+            parse(&text(&[0.0, 2.0, 1.0]), "path.ron").err().as_deref(),
+            Some("path.ron: keyframe times must not decrease")
+        );
+    }
+}
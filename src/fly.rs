@@ -0,0 +1,116 @@
+use crate::camera::{Camera, Direction};
+use nalgebra_glm as glm;
+
+const SPEED_STEP: f32 = 1.2;
+const MIN_SPEED_SCALE: f32 = 0.05;
+const MAX_SPEED_SCALE: f32 = 20.0;
+
+#[derive(Copy, Clone)]
+pub struct Settings {
+    // World units per second squared
+    pub acceleration: f32,
+    // World units per second, before sprinting and the wheel's scale
+    pub max_speed: f32,
+    // Rate at which speed decays once the keys are released, per second
+    pub damping: f32,
+    // Time constant in seconds for mouse look, 0 applies it raw
+    pub mouse_smoothing: f32,
+    pub sprint_multiplier: f32,
+}
+
+// Drives the fly camera frame rate independently: the velocity accelerates
+// towards the requested direction and coasts to a stop when there is none
+pub struct Fly {
+    settings: Settings,
+    velocity: glm::Vec3,
+    // Mouse movement per second
+    look_rate: (f32, f32),
+    speed_scale: f32,
+}
+
+impl Fly {
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        // How far to push in each direction, 1 for a key; negative amounts
+        // push the opposite way
+        movement: &[(Direction, f32)],
+        sprint: bool,
+        mouse: (f32, f32),
+        sensitivity: f32,
+        delta_seconds: f32,
+    ) {
+        if delta_seconds <= 0.0 {
+            return;
+        }
+
+        let blend = if self.settings.mouse_smoothing > 0.0 {
+            1.0 - (-delta_seconds / self.settings.mouse_smoothing).exp()
+        } else {
+            1.0
+        };
+        let rate = (mouse.0 / delta_seconds, mouse.1 / delta_seconds);
+        self.look_rate.0 += (rate.0 - self.look_rate.0) * blend;
+        self.look_rate.1 += (rate.1 - self.look_rate.1) * blend;
+        camera.update_orientation(
+            self.look_rate.0 * delta_seconds,
+            self.look_rate.1 * delta_seconds,
+            sensitivity,
+        );
+
+        let wish = movement
+            .iter()
+            .fold(glm::Vec3::zeros(), |sum, (direction, amount)| {
+                sum + camera.get_direction(*direction) * *amount
+            });
+        if glm::length2(&wish) > 0.0 {
+            let mut max_speed = self.settings.max_speed * self.speed_scale;
+            if sprint {
+                max_speed *= self.settings.sprint_multiplier;
+            }
+            // Partly pushed sticks move slower, but keys together are no faster than one
+            let wish = if glm::length(&wish) > 1.0 {
+                glm::normalize(&wish)
+            } else {
+                wish
+            };
+            let target = wish * max_speed;
+            let change = target - self.velocity;
+            let step = self.settings.acceleration * self.speed_scale * delta_seconds;
+            self.velocity += if glm::length(&change) > step {
+                glm::normalize(&change) * step
+            } else {
+                change
+            };
+        } else {
+            self.velocity *= (-self.settings.damping * delta_seconds).exp();
+        }
+
+        camera.translate(&(self.velocity * delta_seconds));
+    }
+
+    // One step per notch of the mouse wheel, scaling acceleration along with speed
+    pub fn adjust_speed(&mut self, steps: f32) -> f32 {
+        self.speed_scale = num::clamp(
+            self.speed_scale * SPEED_STEP.powf(steps),
+            MIN_SPEED_SCALE,
+            MAX_SPEED_SCALE,
+        );
+        self.settings.max_speed * self.speed_scale
+    }
+
+    // For when something else takes over the camera
+    pub fn stop(&mut self) {
+        self.velocity = glm::Vec3::zeros();
+        self.look_rate = (0.0, 0.0);
+    }
+}
+
+pub fn create(settings: Settings) -> Fly {
+    Fly {
+        settings,
+        velocity: glm::Vec3::zeros(),
+        look_rate: (0.0, 0.0),
+        speed_scale: 1.0,
+    }
+}
@@ -0,0 +1,200 @@
+extern crate gl;
+use gl::types::*;
+
+#[derive(Copy, Clone)]
+pub enum ColourFormat {
+    Rgba16F,
+    // One unsigned integer per pixel, read back with `read_uint`
+    R32Ui,
+}
+
+pub struct Framebuffer {
+    id: GLuint,
+    colour: GLuint,
+    depth: Option<GLuint>,
+    format: ColourFormat,
+    width: u32,
+    height: u32,
+}
+
+impl Framebuffer {
+    pub fn bind(&self) -> Result<(), String> {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+        set_viewport(self.width, self.height)
+    }
+
+    pub fn bind_read(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+        }
+    }
+
+    pub fn bind_colour_texture(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.colour);
+        }
+    }
+
+    // Recreates the attachments, their contents are lost
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        *self = create(width, height, self.format, self.depth.is_some())?;
+        Ok(())
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // Pixels count from the bottom left, outside the framebuffer reads as 0
+    pub fn read_uint(&self, x: u32, y: u32) -> u32 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let mut value: u32 = 0;
+        self.bind_read();
+        unsafe {
+            gl::ReadPixels(
+                x as GLint,
+                y as GLint,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                &mut value as *mut u32 as *mut std::os::raw::c_void,
+            );
+        }
+        value
+    }
+
+    pub fn average_colour(&self) -> nalgebra_glm::Vec3 {
+        let levels = 32 - self.width.max(self.height).leading_zeros();
+        let mut average = [0.0_f32; 4];
+        self.bind_colour_texture();
+        unsafe {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                (levels - 1) as GLint,
+                gl::RGBA,
+                gl::FLOAT,
+                average.as_mut_ptr() as *mut std::os::raw::c_void,
+            );
+        }
+        nalgebra_glm::vec3(average[0], average[1], average[2])
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(depth) = self.depth {
+                gl::DeleteRenderbuffers(1, &depth);
+            }
+            gl::DeleteTextures(1, &self.colour);
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+pub fn create(
+    width: u32,
+    height: u32,
+    format: ColourFormat,
+    with_depth: bool,
+) -> Result<Framebuffer, String> {
+    let gl_width: GLsizei = width.try_into().map_err(error_to_string())?;
+    let gl_height: GLsizei = height.try_into().map_err(error_to_string())?;
+
+    let mut framebuffer = Framebuffer {
+        id: 0,
+        colour: 0,
+        depth: None,
+        format,
+        width,
+        height,
+    };
+    unsafe {
+        gl::GenFramebuffers(1, &mut framebuffer.id);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id);
+
+        gl::GenTextures(1, &mut framebuffer.colour);
+        gl::BindTexture(gl::TEXTURE_2D, framebuffer.colour);
+        // Integer textures can't be filtered
+        let (internal_format, pixel_format, kind, filter) = match format {
+            ColourFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT, gl::LINEAR),
+            ColourFormat::R32Ui => (gl::R32UI, gl::RED_INTEGER, gl::UNSIGNED_INT, gl::NEAREST),
+        };
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            internal_format as i32,
+            gl_width,
+            gl_height,
+            0,
+            pixel_format,
+            kind,
+            std::ptr::null(),
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            framebuffer.colour,
+            0,
+        );
+
+        if with_depth {
+            let mut depth: GLuint = 0;
+            gl::GenRenderbuffers(1, &mut depth);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth);
+            // Floating point, so reversed-Z gets the precision it is there for
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH32F_STENCIL8, gl_width, gl_height);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth,
+            );
+            framebuffer.depth = Some(depth);
+        }
+    }
+
+    let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(format!("Framebuffer is incomplete: 0x{:x}", status));
+    }
+
+    Ok(framebuffer)
+}
+
+pub fn bind_default(width: u32, height: u32) -> Result<(), String> {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    set_viewport(width, height)
+}
+
+fn set_viewport(width: u32, height: u32) -> Result<(), String> {
+    let width: GLsizei = width.try_into().map_err(error_to_string())?;
+    let height: GLsizei = height.try_into().map_err(error_to_string())?;
+    unsafe {
+        gl::Viewport(0, 0, width, height);
+    }
+    Ok(())
+}
+
+fn error_to_string<E>() -> fn(E) -> String
+where
+    E: std::fmt::Display,
+{
+    |e: E| e.to_string()
+}
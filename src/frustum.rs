@@ -0,0 +1,186 @@
+use crate::bounds::Bounds;
+use nalgebra_glm as glm;
+
+// Points on the side the normal faces are inside, ie. where
+// dot(normal, point) + distance >= 0
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub normal: glm::Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn signed_distance(&self, point: &glm::Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+pub struct Frustum {
+    // Left, right, bottom, top, then the planes where depth reaches its minimum
+    // and w: near and far, or far and near with reversed-Z
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // Conservative: a box near a corner of the frustum can pass without being
+    // visible, but a visible one is never rejected
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the normal
+            let corner = glm::vec3(
+                if plane.normal.x >= 0.0 {
+                    bounds.max.x
+                } else {
+                    bounds.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    bounds.max.y
+                } else {
+                    bounds.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    bounds.max.z
+                } else {
+                    bounds.min.z
+                },
+            );
+            plane.signed_distance(&corner) >= 0.0
+        })
+    }
+}
+
+// How many objects one pass drew and skipped
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct Counts {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+impl std::ops::Add for Counts {
+    type Output = Counts;
+
+    fn add(self, other: Counts) -> Counts {
+        Counts {
+            drawn: self.drawn + other.drawn,
+            culled: self.culled + other.culled,
+        }
+    }
+}
+
+// The planes of a view-projection matrix, in world space when it includes the
+// view. Clip space depth runs from 0 to w when `zero_to_one` is set, otherwise
+// from -w to w. An infinite far plane has no normal and never rejects anything.
+pub fn from_matrix(matrix: &glm::Mat4, zero_to_one: bool) -> Frustum {
+    let row = |i: usize| glm::Vec4::from(matrix.row(i).transpose());
+    let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+    let near = if zero_to_one { z } else { w + z };
+    Frustum {
+        planes: [w + x, w - x, w + y, w - y, near, w - z].map(|coefficients| {
+            let normal = coefficients.xyz();
+            let length = normal.norm();
+            if length > f32::EPSILON {
+                Plane {
+                    normal: normal / length,
+                    distance: coefficients.w / length,
+                }
+            } else {
+                Plane {
+                    normal,
+                    distance: coefficients.w,
+                }
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEAR: f32 = 0.5;
+    const FAR: f32 = 50.0;
+
+    fn assert_plane(plane: &Plane, normal: glm::Vec3, distance: f32) {
+        assert!(
+            (plane.normal - normal).norm() < 1e-4 && (plane.distance - distance).abs() < 1e-3,
+            "expected {:?} {}, got {:?}",
+            normal,
+            distance,
+            plane
+        );
+    }
+
+    fn cube_at(centre: glm::Vec3) -> Bounds {
+        Bounds {
+            min: centre - glm::vec3(0.5, 0.5, 0.5),
+            max: centre + glm::vec3(0.5, 0.5, 0.5),
+        }
+    }
+
+    #[test]
+    fn perspective_planes_face_inwards() {
+        let y_fov = std::f32::consts::FRAC_PI_2;
+        let frustum = from_matrix(&glm::perspective(1.0, y_fov, NEAR, FAR), false);
+        let [left, right, bottom, top, near, far] = frustum.planes;
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_plane(&left, glm::vec3(half, 0.0, -half), 0.0);
+        assert_plane(&right, glm::vec3(-half, 0.0, -half), 0.0);
+        assert_plane(&bottom, glm::vec3(0.0, half, -half), 0.0);
+        assert_plane(&top, glm::vec3(0.0, -half, -half), 0.0);
+        assert_plane(&near, glm::vec3(0.0, 0.0, -1.0), -NEAR);
+        assert_plane(&far, glm::vec3(0.0, 0.0, 1.0), FAR);
+    }
+
+    #[test]
+    fn orthographic_planes_sit_at_the_extents() {
+        let frustum = from_matrix(&glm::ortho(-4.0, 4.0, -3.0, 3.0, NEAR, FAR), false);
+        let [left, right, bottom, top, near, far] = frustum.planes;
+
+        assert_plane(&left, glm::vec3(1.0, 0.0, 0.0), 4.0);
+        assert_plane(&right, glm::vec3(-1.0, 0.0, 0.0), 4.0);
+        assert_plane(&bottom, glm::vec3(0.0, 1.0, 0.0), 3.0);
+        assert_plane(&top, glm::vec3(0.0, -1.0, 0.0), 3.0);
+        assert_plane(&near, glm::vec3(0.0, 0.0, -1.0), -NEAR);
+        assert_plane(&far, glm::vec3(0.0, 0.0, 1.0), FAR);
+    }
+
+    #[test]
+    fn planes_include_the_view() {
+        // Looking down +X from (10, 0, 0)
+        let view = glm::look_at(
+            &glm::vec3(10.0, 0.0, 0.0),
+            &glm::vec3(11.0, 0.0, 0.0),
+            &glm::vec3(0.0, 1.0, 0.0),
+        );
+        let projection = glm::perspective(1.0, 1.0, NEAR, FAR);
+        let frustum = from_matrix(&(projection * view), false);
+
+        assert_plane(&frustum.planes[4], glm::vec3(1.0, 0.0, 0.0), -10.0 - NEAR);
+        assert_plane(&frustum.planes[5], glm::vec3(-1.0, 0.0, 0.0), 10.0 + FAR);
+    }
+
+    #[test]
+    fn reversed_infinite_has_no_far_plane() {
+        let projection = glm::reversed_infinite_perspective_rh_zo(1.0, 1.0, NEAR);
+        let frustum = from_matrix(&projection, true);
+
+        // Depth is w at the near plane and falls towards 0 at infinity
+        assert_eq!(frustum.planes[4].normal, glm::Vec3::zeros());
+        assert_plane(&frustum.planes[5], glm::vec3(0.0, 0.0, -1.0), -NEAR);
+        assert!(frustum.intersects(&cube_at(glm::vec3(0.0, 0.0, -1e6))));
+        assert!(!frustum.intersects(&cube_at(glm::vec3(0.0, 0.0, 2.0))));
+    }
+
+    #[test]
+    fn boxes_are_culled_outside() {
+        let frustum = from_matrix(&glm::perspective(1.0, 1.0, NEAR, FAR), false);
+
+        assert!(frustum.intersects(&cube_at(glm::vec3(0.0, 0.0, -10.0))));
+        // Straddling the left plane
+        assert!(frustum.intersects(&cube_at(glm::vec3(-5.5, 0.0, -10.0))));
+        assert!(!frustum.intersects(&cube_at(glm::vec3(-10.0, 0.0, -10.0))));
+        assert!(!frustum.intersects(&cube_at(glm::vec3(0.0, 0.0, 10.0))));
+        assert!(!frustum.intersects(&cube_at(glm::vec3(0.0, 0.0, -100.0))));
+    }
+}
@@ -0,0 +1,94 @@
+use sdl2::controller::{Axis, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+// Fractions of full deflection ignored around the rest positions
+const STICK_DEAD_ZONE: f32 = 0.15;
+const TRIGGER_DEAD_ZONE: f32 = 0.05;
+// Applied to what is left past the dead zone; above 1 gives finer control
+// near the centre while still reaching full speed at the edge
+const RESPONSE_EXPONENT: f32 = 2.0;
+
+// Sticks are x right and y down, like SDL's axes; everything runs 0 to 1 from
+// the edge of the dead zone to full deflection
+#[derive(Default)]
+pub struct State {
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+// Every connected controller, opened and closed as they are plugged in and out
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    controllers: Vec<GameController>,
+}
+
+impl Gamepads {
+    // SDL reports the controllers already connected at startup as added too
+    pub fn handle(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    println!("Gamepad connected: {}", controller.name());
+                    self.controllers.push(controller);
+                }
+                Err(e) => eprintln!("Failed to open gamepad {}: {}", which, e),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers.retain(|controller| {
+                    let removed = controller.instance_id() == which;
+                    if removed {
+                        println!("Gamepad disconnected: {}", controller.name());
+                    }
+                    !removed
+                });
+            }
+            _ => (),
+        }
+    }
+
+    // Summed over every controller, so any of them can drive the camera
+    pub fn read(&self) -> State {
+        let mut state = State::default();
+        for controller in &self.controllers {
+            let axis = |axis| (controller.axis(axis) as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+            let left = shape_stick(axis(Axis::LeftX), axis(Axis::LeftY));
+            let right = shape_stick(axis(Axis::RightX), axis(Axis::RightY));
+            state.left_stick.0 += left.0;
+            state.left_stick.1 += left.1;
+            state.right_stick.0 += right.0;
+            state.right_stick.1 += right.1;
+            state.left_trigger += shape(axis(Axis::TriggerLeft), TRIGGER_DEAD_ZONE);
+            state.right_trigger += shape(axis(Axis::TriggerRight), TRIGGER_DEAD_ZONE);
+        }
+        state
+    }
+}
+
+pub fn create(subsystem: GameControllerSubsystem) -> Gamepads {
+    Gamepads {
+        subsystem,
+        controllers: Vec::new(),
+    }
+}
+
+// The dead zone is radial so diagonals aren't snapped to the axes
+fn shape_stick(x: f32, y: f32) -> (f32, f32) {
+    let magnitude = (x * x + y * y).sqrt();
+    let shaped = shape(magnitude, STICK_DEAD_ZONE);
+    if shaped == 0.0 {
+        return (0.0, 0.0);
+    }
+    (x / magnitude * shaped, y / magnitude * shaped)
+}
+
+fn shape(value: f32, dead_zone: f32) -> f32 {
+    if value <= dead_zone {
+        return 0.0;
+    }
+    ((value - dead_zone) / (1.0 - dead_zone))
+        .min(1.0)
+        .powf(RESPONSE_EXPONENT)
+}
@@ -0,0 +1,421 @@
+use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::material::{self, Material};
+use crate::mesh::{self, Mesh, StandardVertex};
+use crate::scene::{self, Component, NodeId, Scene};
+use crate::texture::{self, Texture};
+use base64::Engine;
+use nalgebra_glm as glm;
+use std::path::Path;
+use std::rc::Rc;
+
+const DIELECTRIC_SPECULAR: f32 = 0.04;
+const MIN_ROUGHNESS: f32 = 0.05;
+
+// Adds the file's default scene under a new node below `parent`, returning that node
+pub fn load(path: &Path, scene: &mut Scene, parent: Option<NodeId>) -> Result<NodeId, String> {
+    let gltf::Gltf { document, blob } =
+        gltf::Gltf::open(path).map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => blob
+                .clone()
+                .ok_or(format!("{} has no binary chunk", path.display())),
+            gltf::buffer::Source::Uri(uri) => read_uri(uri, directory),
+        })
+        .collect::<Result<Vec<Vec<u8>>, String>>()?;
+
+    let mut textures: Vec<Option<Rc<Texture>>> = vec![None; document.images().len()];
+    let mut materials = document
+        .materials()
+        .map(|gltf_material| {
+            create_material(&gltf_material, &buffers, directory, &mut textures).map(Rc::new)
+        })
+        .collect::<Result<Vec<Rc<Material>>, String>>()?;
+
+    let mut default_material = None;
+    let mut meshes: Vec<Vec<(Rc<Mesh>, Rc<Material>)>> =
+        Vec::with_capacity(document.meshes().len());
+    for gltf_mesh in document.meshes() {
+        let mut primitives = Vec::new();
+        for primitive in gltf_mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let (vertices, indices) = read_primitive(&primitive, &buffers)?;
+            let material = match primitive.material().index() {
+                Some(index) => index,
+                None => *default_material.get_or_insert(materials.len()),
+            };
+            if material == materials.len() {
+                materials.push(Rc::new(material::create(
+                    Rc::new(texture::from_colour([1.0, 1.0, 1.0])?),
+                    Rc::new(texture::from_colour([DIELECTRIC_SPECULAR; 3])?),
+                    shininess_from_roughness(1.0),
+                )));
+            }
+
+            let indices = mesh::compact_indices(indices, vertices.len());
+            primitives.push((
+                Rc::new(mesh::create(&vertices, Some(&indices))?),
+                materials[material].clone(),
+            ));
+        }
+        meshes.push(primitives);
+    }
+
+    let gltf_scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or(format!("{} contains no scenes", path.display()))?;
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let root = scene.add_node(&name, parent, scene::identity());
+    for node in gltf_scene.nodes() {
+        add_node(scene, &meshes, &node, root);
+    }
+    Ok(root)
+}
+
+fn add_node(
+    scene: &mut Scene,
+    meshes: &[Vec<(Rc<Mesh>, Rc<Material>)>],
+    gltf_node: &gltf::Node,
+    parent: NodeId,
+) {
+    let (translation, [x, y, z, w], scale) = gltf_node.transform().decomposed();
+    let id = scene.add_node(
+        gltf_node.name().unwrap_or(""),
+        Some(parent),
+        scene::Transform {
+            translation: glm::Vec3::from(translation),
+            rotation: glm::quat(x, y, z, w),
+            scale: glm::Vec3::from(scale),
+        },
+    );
+
+    if let Some(gltf_mesh) = gltf_node.mesh() {
+        for (mesh, material) in &meshes[gltf_mesh.index()] {
+            scene.add_component(
+                id,
+                Component::Renderable {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                },
+            );
+        }
+    }
+    if let Some(camera) = gltf_node.camera() {
+        let y_fov = match camera.projection() {
+            gltf::camera::Projection::Perspective(perspective) => Some(perspective.yfov()),
+            gltf::camera::Projection::Orthographic(_) => None,
+        };
+        scene.add_component(id, Component::Camera { y_fov });
+    }
+    if let Some(light) = gltf_node.light() {
+        scene.add_component(id, create_light(&light));
+    }
+
+    for child in gltf_node.children() {
+        add_node(scene, meshes, &child, id);
+    }
+}
+
+// Intensities are photometric (candela for point and spot lights, lux for
+// directional ones), so positional lights fall off with the inverse square.
+// Their range is ignored: the lighting shader has no cutoff distance, so they
+// reach everything. Lights sit at their node's origin and shine down its -Z axis.
+fn create_light(light: &gltf::khr_lights_punctual::Light) -> Component {
+    let colour = glm::Vec3::from(light.color()) * light.intensity();
+    let direction = glm::vec3(0.0, 0.0, -1.0);
+    match light.kind() {
+        gltf::khr_lights_punctual::Kind::Directional => {
+            Component::DirectionalLight(DirectionalLight {
+                direction,
+                ambient: colour * 0.05,
+                diffuse: colour,
+                specular: colour,
+            })
+        }
+        gltf::khr_lights_punctual::Kind::Point => Component::PointLight(PointLight {
+            position: glm::Vec3::zeros(),
+            colour,
+            attenuation_constant: 1.0,
+            attenuation_linear: 0.0,
+            attenuation_quadratic: 1.0,
+            casts_shadows: false,
+        }),
+        gltf::khr_lights_punctual::Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => Component::SpotLight(SpotLight {
+            position: glm::Vec3::zeros(),
+            direction,
+            inner_cutoff: num::Float::to_degrees(inner_cone_angle),
+            outer_cutoff: num::Float::to_degrees(outer_cone_angle),
+            attenuation_constant: 1.0,
+            attenuation_linear: 0.0,
+            attenuation_quadratic: 1.0,
+            ambient: colour * 0.05,
+            diffuse: colour,
+            specular: colour,
+        }),
+    }
+}
+
+fn read_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[Vec<u8>],
+) -> Result<(Vec<StandardVertex>, Vec<u32>), String> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+    let mut vertices: Vec<StandardVertex> = reader
+        .read_positions()
+        .ok_or("Primitive has no positions")?
+        .map(|position| StandardVertex {
+            position,
+            normal: [0.0, 0.0, 0.0],
+            texture_coords: [0.0, 0.0],
+        })
+        .collect();
+    if let Some(texture_coords) = reader.read_tex_coords(0) {
+        for (vertex, texture_coords) in vertices.iter_mut().zip(texture_coords.into_f32()) {
+            vertex.texture_coords = texture_coords;
+        }
+    }
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertices.len())
+    {
+        return Err(format!(
+            "Primitive index {} out of range for {} vertices",
+            index,
+            vertices.len()
+        ));
+    }
+
+    match reader.read_normals() {
+        Some(normals) => {
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                vertex.normal = normal;
+            }
+        }
+        None => mesh::generate_normals(&mut vertices, &indices),
+    }
+    Ok((vertices, indices))
+}
+
+// The lighting shader is Blinn-Phong, so the metallic-roughness parameters are
+// approximated: metals tint their specular by the base colour and lose their
+// diffuse, and roughness maps onto an equivalent specular exponent.
+fn create_material(
+    gltf_material: &gltf::Material,
+    buffers: &[Vec<u8>],
+    directory: &Path,
+    textures: &mut [Option<Rc<Texture>>],
+) -> Result<Material, String> {
+    let pbr = gltf_material.pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let base_colour = glm::vec3(r, g, b);
+    let metallic = pbr.metallic_factor();
+
+    // glTF multiplies the base colour texture by the factor
+    let diffuse_colour = base_colour * (1.0 - metallic);
+    let (diffuse, tint) = match pbr.base_color_texture() {
+        Some(info) => (
+            load_image(&info.texture().source(), buffers, directory, textures)?,
+            diffuse_colour,
+        ),
+        None => (
+            Rc::new(texture::from_colour(diffuse_colour.into())?),
+            glm::vec3(1.0, 1.0, 1.0),
+        ),
+    };
+    let specular = glm::lerp(
+        &glm::vec3(
+            DIELECTRIC_SPECULAR,
+            DIELECTRIC_SPECULAR,
+            DIELECTRIC_SPECULAR,
+        ),
+        &base_colour,
+        metallic,
+    );
+
+    Ok(material::create(
+        diffuse,
+        Rc::new(texture::from_colour(specular.into())?),
+        shininess_from_roughness(pbr.roughness_factor()),
+    )
+    .with_tint(tint))
+}
+
+fn shininess_from_roughness(roughness: f32) -> f32 {
+    let alpha = roughness.max(MIN_ROUGHNESS).powi(2);
+    (2.0 / (alpha * alpha) - 2.0).max(1.0)
+}
+
+fn load_image(
+    image: &gltf::Image,
+    buffers: &[Vec<u8>],
+    directory: &Path,
+    textures: &mut [Option<Rc<Texture>>],
+) -> Result<Rc<Texture>, String> {
+    if let Some(texture) = &textures[image.index()] {
+        return Ok(texture.clone());
+    }
+
+    let texture = Rc::new(match image.source() {
+        gltf::image::Source::View { view, .. } => {
+            let (start, end) = (view.offset(), view.offset() + view.length());
+            let bytes = buffers
+                .get(view.buffer().index())
+                .and_then(|buffer| buffer.get(start..end))
+                .ok_or_else(|| {
+                    format!(
+                        "Image {} reads bytes {}..{} past the end of buffer {}",
+                        image.index(),
+                        start,
+                        end,
+                        view.buffer().index()
+                    )
+                })?;
+            texture::create(bytes)?
+        }
+        gltf::image::Source::Uri { uri, .. } => texture::create(&read_uri(uri, directory)?)?,
+    });
+    textures[image.index()] = Some(texture.clone());
+    Ok(texture)
+}
+
+fn read_uri(uri: &str, directory: &Path) -> Result<Vec<u8>, String> {
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (_, encoded) = data
+                .split_once(";base64,")
+                .ok_or(format!("Unsupported data URI: {}", uri))?;
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(error_to_string())
+        }
+        None => {
+            let path = directory.join(percent_decode(uri)?);
+            std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        }
+    }
+}
+
+// Relative URIs escape spaces and other reserved characters as %XX
+fn percent_decode(uri: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let escape = after
+                .get(..2)
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+                .ok_or(format!("Invalid escape in URI: {}", uri))?;
+            bytes.push(escape);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("URI is not UTF-8 once decoded: {}", uri))
+}
+
+fn error_to_string<E>() -> fn(E) -> String
+where
+    E: std::fmt::Display,
+{
+    |e: E| e.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One triangle whose positions and indices share a single embedded buffer:
+    // three vec3 positions followed by three u16 indices
+    fn triangle(indices: [u16; 3]) -> gltf::Gltf {
+        let mut buffer = Vec::new();
+        for position in [[0.0_f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for component in position {
+                buffer.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+        for index in indices {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{"byteLength": 42, "uri": "data:application/octet-stream;base64,{}"}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0]}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}]
+            }}"#,
+            base64::engine::general_purpose::STANDARD.encode(&buffer)
+        );
+        gltf::Gltf::from_slice(json.as_bytes()).unwrap()
+    }
+
+    fn read_triangle(indices: [u16; 3]) -> Result<(Vec<StandardVertex>, Vec<u32>), String> {
+        let gltf = triangle(indices);
+        let buffers = vec![read_uri(
+            match gltf.buffers().next().unwrap().source() {
+                gltf::buffer::Source::Uri(uri) => uri,
+                gltf::buffer::Source::Bin => unreachable!(),
+            },
+            Path::new(""),
+        )?];
+        let primitive = gltf.meshes().next().unwrap().primitives().next().unwrap();
+        read_primitive(&primitive, &buffers)
+    }
+
+    #[test]
+    fn primitive_indices_are_read() {
+        let (vertices, indices) = read_triangle([0, 1, 2]).unwrap();
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2]);
+        // Generated, as the primitive has none
+        assert_eq!(vertices[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        assert_eq!(
+            read_triangle([0, 1, 5]).err().as_deref(),
+            Some("Primitive index 5 out of range for 3 vertices")
+        );
+    }
+
+    #[test]
+    fn uris_are_percent_decoded() {
+        assert_eq!(
+            percent_decode("My%20Model/base%5fcolour.png").as_deref(),
+            Ok("My Model/base_colour.png")
+        );
+        assert_eq!(percent_decode("plain.bin").as_deref(), Ok("plain.bin"));
+        assert!(percent_decode("broken%2").is_err());
+        assert!(percent_decode("broken%zz").is_err());
+    }
+}
@@ -0,0 +1,14 @@
+#version 330 core
+
+uniform sampler2D uImage;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    vec3 colour = texture(uImage, aTextureCoords).rgb;
+    float luminance = dot(colour, vec3(0.2126, 0.7152, 0.0722));
+    aFragColours = vec4(vec3(luminance), 1.0);
+}
@@ -0,0 +1,372 @@
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    // Held
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    RollLeft,
+    RollRight,
+    OrbitRotate,
+    OrbitPan,
+    // Pressed
+    Select,
+    ToggleFreeCamera,
+    ToggleProjection,
+    ToggleOrbit,
+    FrameScene,
+    ToggleCursor,
+    TogglePicking,
+    RecordKeyframe,
+    TogglePlayback,
+    ToggleLooping,
+    ClearPath,
+    SavePath,
+    LoadPath,
+    ToggleFlashlight,
+    CycleToneMapping,
+    ToggleAutoExposure,
+    IncreaseExposure,
+    DecreaseExposure,
+    ToggleBloom,
+    DecreaseBloomThreshold,
+    IncreaseBloomThreshold,
+    DecreaseBloomIntensity,
+    IncreaseBloomIntensity,
+    ToggleInstancing,
+    ToggleCulling,
+    // Index into the post-processing pipeline
+    TogglePostPass(usize),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum Source {
+    Key(Keycode),
+    Mouse(MouseButton),
+    Gamepad(Button),
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+}
+
+impl Modifier {
+    fn keys(&self) -> [Keycode; 2] {
+        match self {
+            Modifier::Ctrl => [Keycode::LCtrl, Keycode::RCtrl],
+            Modifier::Shift => [Keycode::LShift, Keycode::RShift],
+            Modifier::Alt => [Keycode::LAlt, Keycode::RAlt],
+        }
+    }
+}
+
+// Written as a string, see default_bindings.ron
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+struct Binding {
+    source: Source,
+    modifiers: Vec<Modifier>,
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Binding, String> {
+        let mut rest = text.as_str();
+        let mut modifiers = Vec::new();
+        while let Some((modifier, after)) = [
+            ("Ctrl+", Modifier::Ctrl),
+            ("Shift+", Modifier::Shift),
+            ("Alt+", Modifier::Alt),
+        ]
+        .iter()
+        .find_map(|(prefix, modifier)| Some((*modifier, rest.strip_prefix(prefix)?)))
+        {
+            modifiers.push(modifier);
+            rest = after;
+        }
+
+        let source = if let Some(name) = rest.strip_prefix("Mouse ") {
+            Source::Mouse(match name {
+                "Left" => MouseButton::Left,
+                "Middle" => MouseButton::Middle,
+                "Right" => MouseButton::Right,
+                "X1" => MouseButton::X1,
+                "X2" => MouseButton::X2,
+                _ => return Err(format!("unknown mouse button `{}`", name)),
+            })
+        } else if let Some(name) = rest.strip_prefix("Pad ") {
+            Source::Gamepad(
+                Button::from_string(name).ok_or(format!("unknown gamepad button `{}`", name))?,
+            )
+        } else {
+            Source::Key(Keycode::from_name(rest).ok_or(format!("unknown key `{}`", rest))?)
+        };
+        Ok(Binding { source, modifiers })
+    }
+}
+
+// Turns events into actions and tracks which held actions are active
+pub struct InputMap {
+    bindings: Vec<(Action, Binding)>,
+    // Inputs currently down and the actions each one started
+    held: HashMap<Source, Vec<Action>>,
+    // Every key down, bound or not, for checking modifiers
+    held_keys: HashSet<Keycode>,
+}
+
+impl InputMap {
+    // The actions this event starts. Key repeats start nothing, so toggles
+    // don't flicker while their key is held.
+    pub fn handle(&mut self, event: &Event) -> Vec<Action> {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(key),
+                repeat: false,
+                ..
+            } => {
+                self.held_keys.insert(key);
+                self.press(Source::Key(key))
+            }
+            Event::KeyUp {
+                keycode: Some(key), ..
+            } => {
+                self.held_keys.remove(&key);
+                self.release(Source::Key(key))
+            }
+            Event::MouseButtonDown { mouse_btn, .. } => self.press(Source::Mouse(mouse_btn)),
+            Event::MouseButtonUp { mouse_btn, .. } => self.release(Source::Mouse(mouse_btn)),
+            Event::ControllerButtonDown { button, .. } => self.press(Source::Gamepad(button)),
+            Event::ControllerButtonUp { button, .. } => self.release(Source::Gamepad(button)),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.values().any(|actions| actions.contains(&action))
+    }
+
+    // Of the bindings for this input whose modifiers are all down, only the
+    // ones needing the most modifiers apply, so Ctrl+S can be bound apart from S
+    fn press(&mut self, source: Source) -> Vec<Action> {
+        let matching: Vec<&(Action, Binding)> = self
+            .bindings
+            .iter()
+            .filter(|(_, binding)| {
+                binding.source == source
+                    && binding.modifiers.iter().all(|modifier| {
+                        modifier
+                            .keys()
+                            .iter()
+                            .any(|key| self.held_keys.contains(key))
+                    })
+            })
+            .collect();
+        let most = matching
+            .iter()
+            .map(|(_, binding)| binding.modifiers.len())
+            .max();
+        let actions: Vec<Action> = matching
+            .iter()
+            .filter(|(_, binding)| Some(binding.modifiers.len()) == most)
+            .map(|(action, _)| *action)
+            .collect();
+        self.held.insert(source, actions.clone());
+        actions
+    }
+
+    // Releasing starts nothing
+    fn release(&mut self, source: Source) -> Vec<Action> {
+        self.held.remove(&source);
+        Vec::new()
+    }
+}
+
+// The default bindings, with any actions listed in `path` rebound to its bindings
+pub fn load(path: Option<&Path>) -> Result<InputMap, String> {
+    match path {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            create(Some((&text, &path.display().to_string())))
+        }
+        None => create(None),
+    }
+}
+
+// `overrides` is the text of a bindings file and the name to report errors against
+fn create(overrides: Option<(&str, &str)>) -> Result<InputMap, String> {
+    let mut bindings = parse(include_str!("default_bindings.ron"), "default_bindings.ron")?;
+    if let Some((text, origin)) = overrides {
+        bindings.extend(parse(text, origin)?);
+    }
+    Ok(InputMap {
+        bindings: bindings
+            .into_iter()
+            .flat_map(|(action, bindings)| {
+                bindings.into_iter().map(move |binding| (action, binding))
+            })
+            .collect(),
+        held: HashMap::new(),
+        held_keys: HashSet::new(),
+    })
+}
+
+fn parse(text: &str, origin: &str) -> Result<BTreeMap<Action, Vec<Binding>>, String> {
+    let mut deserializer =
+        ron::Deserializer::from_str(text).map_err(|e| format!("{}:{}", origin, e))?;
+    let bindings = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let error = deserializer.span_error(e.into_inner());
+        format!(
+            "{}:{}:{}: {}: {}",
+            origin, error.position.line, error.position.col, path, error.code
+        )
+    })?;
+    deserializer.end().map_err(|e| {
+        let error = deserializer.span_error(e);
+        format!(
+            "{}:{}:{}: {}",
+            origin, error.position.line, error.position.col, error.code
+        )
+    })?;
+    Ok(bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+
+    fn key_down(key: Keycode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn key_up(key: Keycode) -> Event {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn binding(text: &str) -> Result<Binding, String> {
+        Binding::try_from(text.to_string())
+    }
+
+    #[test]
+    fn bindings_are_parsed() {
+        let save = binding("Ctrl+S").unwrap();
+        assert!(save.source == Source::Key(Keycode::S));
+        assert!(save.modifiers == [Modifier::Ctrl]);
+
+        let reload = binding("Ctrl+Shift+F5").unwrap();
+        assert!(reload.source == Source::Key(Keycode::F5));
+        assert!(reload.modifiers == [Modifier::Ctrl, Modifier::Shift]);
+
+        assert!(binding("Left Alt").unwrap().source == Source::Key(Keycode::LAlt));
+        assert!(binding("Mouse Left").unwrap().source == Source::Mouse(MouseButton::Left));
+        assert!(binding("Pad a").unwrap().source == Source::Gamepad(Button::A));
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert_eq!(
+            binding("Mouse Thumb").err().as_deref(),
+            Some("unknown mouse button `Thumb`")
+        );
+        assert_eq!(
+            binding("Pad z").err().as_deref(),
+            Some("unknown gamepad button `z`")
+        );
+        assert_eq!(
+            binding("Hyper+S").err().as_deref(),
+            Some("unknown key `Hyper+S`")
+        );
+    }
+
+    #[test]
+    fn held_actions_last_until_release() {
+        let mut input = create(None).unwrap();
+        assert_eq!(input.handle(&key_down(Keycode::W)), [Action::MoveForward]);
+        assert!(input.is_held(Action::MoveForward));
+        // Repeats start nothing
+        let repeat = Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(Keycode::W),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: true,
+        };
+        assert!(input.handle(&repeat).is_empty());
+        assert!(input.handle(&key_up(Keycode::W)).is_empty());
+        assert!(!input.is_held(Action::MoveForward));
+    }
+
+    #[test]
+    fn most_modifiers_win() {
+        let mut input = create(None).unwrap();
+        // Modifiers on their own aren't bound to anything
+        assert!(input.handle(&key_down(Keycode::LCtrl)).is_empty());
+        assert_eq!(input.handle(&key_down(Keycode::S)), [Action::SavePath]);
+        assert!(!input.is_held(Action::MoveBackward));
+        input.handle(&key_up(Keycode::S));
+        input.handle(&key_up(Keycode::LCtrl));
+
+        assert_eq!(input.handle(&key_down(Keycode::S)), [Action::MoveBackward]);
+        assert!(input.is_held(Action::MoveBackward));
+    }
+
+    #[test]
+    fn overrides_replace_only_the_actions_they_list() {
+        let mut input = create(Some((
+            r#"{ MoveForward: ["Up", "Pad dpup"], ToggleFlashlight: [] }"#,
+            "test.ron",
+        )))
+        .unwrap();
+        assert!(input.handle(&key_down(Keycode::W)).is_empty());
+        assert_eq!(input.handle(&key_down(Keycode::Up)), [Action::MoveForward]);
+        assert!(input.handle(&key_down(Keycode::F)).is_empty());
+        assert_eq!(input.handle(&key_down(Keycode::A)), [Action::MoveLeft]);
+    }
+
+    #[test]
+    fn override_errors_give_their_location() {
+        let error = create(Some((
+            "{\n    MoveForward: [\"Up\"],\n    MoveBackward: [\"Nope\"],\n}",
+            "test.ron",
+        )))
+        .err()
+        .unwrap();
+        assert!(
+            error.starts_with("test.ron:3:") && error.ends_with("unknown key `Nope`"),
+            "{}",
+            error
+        );
+    }
+}
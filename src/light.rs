@@ -0,0 +1,107 @@
+use crate::shader::Shader;
+use nalgebra_glm as glm;
+
+pub const NUM_POINT_LIGHTS: usize = 4;
+
+#[derive(Clone)]
+pub struct DirectionalLight {
+    pub direction: glm::Vec3,
+    pub ambient: glm::Vec3,
+    pub diffuse: glm::Vec3,
+    pub specular: glm::Vec3,
+}
+
+impl DirectionalLight {
+    pub fn apply(&self, shader: &Shader) -> Result<(), String> {
+        shader.set_vec3("uDirectionalLight.direction", &self.direction)?;
+        shader.set_vec3("uDirectionalLight.ambient", &self.ambient)?;
+        shader.set_vec3("uDirectionalLight.diffuse", &self.diffuse)?;
+        shader.set_vec3("uDirectionalLight.specular", &self.specular)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct PointLight {
+    pub position: glm::Vec3,
+    pub colour: glm::Vec3,
+    pub attenuation_constant: f32,
+    pub attenuation_linear: f32,
+    pub attenuation_quadratic: f32,
+    pub casts_shadows: bool,
+}
+
+impl PointLight {
+    pub fn apply(&self, shader: &Shader, index: usize) -> Result<(), String> {
+        let uniform = |field: &str| format!("uPointLights[{}].{}", index, field);
+
+        shader.set_vec3(uniform("position").as_str(), &self.position)?;
+        shader.set_vec3(uniform("ambient").as_str(), &(self.colour * 0.05))?;
+        shader.set_vec3(uniform("diffuse").as_str(), &(self.colour * 0.8))?;
+        shader.set_vec3(uniform("specular").as_str(), &self.colour)?;
+        shader.set_float(
+            uniform("attenuation_constant").as_str(),
+            self.attenuation_constant,
+        )?;
+        shader.set_float(
+            uniform("attenuation_linear").as_str(),
+            self.attenuation_linear,
+        )?;
+        shader.set_float(
+            uniform("attenuation_quadratic").as_str(),
+            self.attenuation_quadratic,
+        )?;
+        shader.set_int(uniform("casts_shadows").as_str(), self.casts_shadows as i32)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SpotLight {
+    pub position: glm::Vec3,
+    pub direction: glm::Vec3,
+    pub inner_cutoff: f32,
+    pub outer_cutoff: f32,
+    pub attenuation_constant: f32,
+    pub attenuation_linear: f32,
+    pub attenuation_quadratic: f32,
+    pub ambient: glm::Vec3,
+    pub diffuse: glm::Vec3,
+    pub specular: glm::Vec3,
+}
+
+impl SpotLight {
+    pub fn apply(&self, shader: &Shader) -> Result<(), String> {
+        shader.set_vec3("uSpotLight.position", &self.position)?;
+        shader.set_vec3("uSpotLight.direction", &self.direction)?;
+        shader.set_float(
+            "uSpotLight.inner_cutoff",
+            num::Float::to_radians(self.inner_cutoff).cos(),
+        )?;
+        shader.set_float(
+            "uSpotLight.outer_cutoff",
+            num::Float::to_radians(self.outer_cutoff).cos(),
+        )?;
+        shader.set_float("uSpotLight.attenuation_constant", self.attenuation_constant)?;
+        shader.set_float("uSpotLight.attenuation_linear", self.attenuation_linear)?;
+        shader.set_float(
+            "uSpotLight.attenuation_quadratic",
+            self.attenuation_quadratic,
+        )?;
+        shader.set_vec3("uSpotLight.ambient", &self.ambient)?;
+        shader.set_vec3("uSpotLight.diffuse", &self.diffuse)?;
+        shader.set_vec3("uSpotLight.specular", &self.specular)?;
+        Ok(())
+    }
+}
+
+pub fn point(position: glm::Vec3, colour: glm::Vec3, casts_shadows: bool) -> PointLight {
+    PointLight {
+        position,
+        colour,
+        attenuation_constant: 1.0,
+        attenuation_linear: 0.09,
+        attenuation_quadratic: 0.032,
+        casts_shadows,
+    }
+}
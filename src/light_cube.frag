@@ -1,8 +1,11 @@
 #version 330 core
 
+uniform vec3 uColour;
+uniform float uIntensity;
+
 out vec4 aFragColours;
 
 void main()
 {
-    aFragColours = vec4(1.0);
-}
\ No newline at end of file
+    aFragColours = vec4(uColour * uIntensity, 1.0);
+}
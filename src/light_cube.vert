@@ -2,10 +2,9 @@
 layout (location = 0) in vec3 inVertices;
 
 uniform mat4 uModel;
-uniform mat4 uView;
-uniform mat4 uProjection;
+uniform mat4 uViewProjection;
 
 void main()
 {
-    gl_Position = uProjection * uView * uModel * vec4(inVertices, 1.0);
-}
\ No newline at end of file
+    gl_Position = uViewProjection * uModel * vec4(inVertices, 1.0);
+}
@@ -3,6 +3,7 @@ struct Material {
     sampler2D diffuse;
     sampler2D specular;
     float shininess;
+    vec3 tint;
 };
 
 struct DirectionalLight {
@@ -23,6 +24,8 @@ struct PointLight {
     vec3 ambient;
     vec3 diffuse;
     vec3 specular;
+
+    bool casts_shadows;
 };
 
 struct SpotLight {
@@ -42,6 +45,7 @@ struct SpotLight {
 };
 
 #define NUM_POINT_LIGHTS 4
+#define MAX_CASCADES 4
 
 uniform Material uMaterial;
 uniform DirectionalLight uDirectionalLight;
@@ -50,15 +54,60 @@ uniform SpotLight uSpotLight;
 uniform vec3 uViewPos;
 uniform int uFlashlight;
 
+uniform sampler2DArray uShadowMap;
+uniform mat4 uLightSpaces[MAX_CASCADES];
+uniform float uCascadeSplits[MAX_CASCADES];
+uniform int uCascadeCount;
+uniform float uCascadeBlend;
+uniform int uShadowPcfRadius;
+uniform float uShadowBiasConstant;
+uniform float uShadowBiasSlope;
+
+uniform samplerCube uPointShadowMaps[NUM_POINT_LIGHTS];
+uniform float uPointShadowFar;
+
+uniform sampler2DArray uSpotShadowMap;
+uniform mat4 uSpotLightSpace;
+uniform float uSpotShadowNear;
+uniform float uSpotShadowFar;
+uniform float uSpotLightSize;
+uniform float uSpotShadowBias;
+
+const vec3 POINT_SHADOW_OFFSETS[20] = vec3[](
+    vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+    vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+    vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+    vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+    vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+const vec2 POISSON_DISK[16] = vec2[](
+    vec2(-0.94201624, -0.39906216), vec2(0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870), vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432), vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845), vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554), vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023), vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507), vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367), vec2(0.14383161, -0.14100790)
+);
+
 in vec3 aNormal;
 in vec3 aFragPos;
 in vec2 aTextureCoords;
+in float aViewDepth;
 
 out vec4 aFragColours;
 
 vec3 compute_directional_lighting(DirectionalLight light, vec3 normal, vec3 view_dir);
-vec3 compute_point_lighting(PointLight light, vec3 normal, vec3 frag_pos, vec3 view_dir);
+float compute_directional_shadow(vec3 normal, vec3 light_dir);
+float sample_cascade(int cascade, vec3 normal, vec3 light_dir);
+vec3 compute_point_lighting(PointLight light, int index, vec3 normal, vec3 frag_pos, vec3 view_dir);
+float compute_point_shadow(PointLight light, int index, vec3 frag_pos);
+float sample_point_shadow_map(int index, vec3 direction);
 vec3 compute_spot_lighting(SpotLight light, vec3 normal, vec3 frag_pos, vec3 view_dir);
+float compute_spot_shadow(vec3 frag_pos);
+float linearise_spot_depth(float depth);
 
 void main()
 {
@@ -67,7 +116,7 @@ void main()
 
     vec3 result = compute_directional_lighting(uDirectionalLight, normal, view_dir);
     for (int i = 0; i != NUM_POINT_LIGHTS; ++i) {
-        result += compute_point_lighting(uPointLights[i], normal, aFragPos, view_dir);
+        result += compute_point_lighting(uPointLights[i], i, normal, aFragPos, view_dir);
     }
 
     if (uFlashlight > 0) {
@@ -85,17 +134,74 @@ vec3 compute_directional_lighting(DirectionalLight light, vec3 normal, vec3 view
     float diffuse_factor = max(dot(normal, light_dir), 0.0);
     float specular_factor = pow(max(dot(view_dir, reflect_dir), 0.0), uMaterial.shininess);
 
-    vec3 diffuse_colours = texture(uMaterial.diffuse, aTextureCoords).rgb;
+    vec3 diffuse_colours = texture(uMaterial.diffuse, aTextureCoords).rgb * uMaterial.tint;
     vec3 specular_colours = texture(uMaterial.specular, aTextureCoords).rgb;
 
     vec3 ambient = light.ambient * diffuse_colours;
     vec3 diffuse = light.diffuse * diffuse_factor * diffuse_colours;
     vec3 specular = light.specular * specular_factor * specular_colours;
 
-    return (ambient + diffuse + specular);
+    float shadow = compute_directional_shadow(normal, light_dir);
+
+    return (ambient + (1.0 - shadow) * (diffuse + specular));
+}
+
+float compute_directional_shadow(vec3 normal, vec3 light_dir)
+{
+    int cascade = uCascadeCount;
+    for (int i = 0; i != uCascadeCount; ++i) {
+        if (aViewDepth < uCascadeSplits[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if (cascade == uCascadeCount) {
+        return 0.0;
+    }
+
+    float shadow = sample_cascade(cascade, normal, light_dir);
+
+    // Fade into the next cascade over the far end of this one to hide the seam
+    float cascade_near = (cascade == 0) ? 0.0 : uCascadeSplits[cascade - 1];
+    float cascade_far = uCascadeSplits[cascade];
+    float blend_start = cascade_far - uCascadeBlend * (cascade_far - cascade_near);
+    if (aViewDepth > blend_start) {
+        float next_shadow = (cascade + 1 < uCascadeCount) ? sample_cascade(cascade + 1, normal, light_dir) : 0.0;
+        shadow = mix(shadow, next_shadow, (aViewDepth - blend_start) / (cascade_far - blend_start));
+    }
+
+    return shadow;
 }
 
-vec3 compute_point_lighting(PointLight light, vec3 normal, vec3 frag_pos, vec3 view_dir)
+float sample_cascade(int cascade, vec3 normal, vec3 light_dir)
+{
+    vec4 frag_pos_light_space = uLightSpaces[cascade] * vec4(aFragPos, 1.0);
+    vec3 coords = frag_pos_light_space.xyz / frag_pos_light_space.w;
+    coords = coords * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 0.0;
+    }
+
+    // Slope-scaled bias: grazing surfaces need a larger offset to avoid acne
+    float cos_theta = clamp(dot(normal, light_dir), 0.0, 1.0);
+    float tan_theta = sqrt(1.0 - cos_theta * cos_theta) / max(cos_theta, 0.05);
+    float bias = uShadowBiasConstant + uShadowBiasSlope * min(tan_theta, 10.0);
+
+    vec2 texel = 1.0 / vec2(textureSize(uShadowMap, 0).xy);
+    float shadow = 0.0;
+    for (int x = -uShadowPcfRadius; x <= uShadowPcfRadius; ++x) {
+        for (int y = -uShadowPcfRadius; y <= uShadowPcfRadius; ++y) {
+            vec2 offset = vec2(x, y) * texel;
+            float depth = texture(uShadowMap, vec3(coords.xy + offset, float(cascade))).r;
+            shadow += (coords.z - bias > depth) ? 1.0 : 0.0;
+        }
+    }
+
+    float kernel_width = float(2 * uShadowPcfRadius + 1);
+    return shadow / (kernel_width * kernel_width);
+}
+
+vec3 compute_point_lighting(PointLight light, int index, vec3 normal, vec3 frag_pos, vec3 view_dir)
 {
     vec3 light_dir = normalize(light.position - frag_pos);
     vec3 reflect_dir = reflect(-light_dir, normal);
@@ -105,16 +211,54 @@ vec3 compute_point_lighting(PointLight light, vec3 normal, vec3 frag_pos, vec3 v
     float distance = length(light.position - frag_pos);
     float attenuation = 1.0 / (light.attenuation_constant + light.attenuation_linear * distance + light.attenuation_quadratic * distance * distance);
 
-    vec3 diffuse_colours = texture(uMaterial.diffuse, aTextureCoords).rgb;
+    vec3 diffuse_colours = texture(uMaterial.diffuse, aTextureCoords).rgb * uMaterial.tint;
     vec3 specular_colours = texture(uMaterial.specular, aTextureCoords).rgb;
 
     vec3 ambient = light.ambient * diffuse_colours;
     vec3 diffuse = light.diffuse * diffuse_factor * diffuse_colours;
     vec3 specular = light.specular * specular_factor * specular_colours;
 
-    return attenuation * (ambient + diffuse + specular);
+    float shadow = light.casts_shadows ? compute_point_shadow(light, index, frag_pos) : 0.0;
+
+    return attenuation * (ambient + (1.0 - shadow) * (diffuse + specular));
+}
+
+float compute_point_shadow(PointLight light, int index, vec3 frag_pos)
+{
+    vec3 light_to_frag = frag_pos - light.position;
+    float current_depth = length(light_to_frag);
+    if (current_depth > uPointShadowFar) {
+        return 0.0;
+    }
+
+    // Widen the sampling disk with view distance so far shadows stay soft
+    float view_distance = length(uViewPos - frag_pos);
+    float disk_radius = (1.0 + view_distance / uPointShadowFar) / 50.0;
+    float bias = 0.05;
+
+    float shadow = 0.0;
+    for (int i = 0; i != 20; ++i) {
+        vec3 direction = light_to_frag + POINT_SHADOW_OFFSETS[i] * disk_radius;
+        float closest_depth = sample_point_shadow_map(index, direction) * uPointShadowFar;
+        shadow += (current_depth - bias > closest_depth) ? 1.0 : 0.0;
+    }
+    return shadow / 20.0;
 }
 
+float sample_point_shadow_map(int index, vec3 direction)
+{
+    // GLSL 3.30 only allows constant indices into sampler arrays
+    if (index == 0) {
+        return texture(uPointShadowMaps[0], direction).r;
+    } else if (index == 1) {
+        return texture(uPointShadowMaps[1], direction).r;
+    } else if (index == 2) {
+        return texture(uPointShadowMaps[2], direction).r;
+    }
+    return texture(uPointShadowMaps[3], direction).r;
+}
+
+
 vec3 compute_spot_lighting(SpotLight light, vec3 normal, vec3 frag_pos, vec3 view_dir)
 {
     vec3 light_dir = normalize(light.position - frag_pos);
@@ -130,12 +274,64 @@ vec3 compute_spot_lighting(SpotLight light, vec3 normal, vec3 frag_pos, vec3 vie
     float distance = length(light.position - frag_pos);
     float attenuation = 1.0 / (light.attenuation_constant + light.attenuation_linear * distance + light.attenuation_quadratic * distance * distance);
 
-    vec3 diffuse_colours = texture(uMaterial.diffuse, aTextureCoords).rgb;
+    vec3 diffuse_colours = texture(uMaterial.diffuse, aTextureCoords).rgb * uMaterial.tint;
     vec3 specular_colours = texture(uMaterial.specular, aTextureCoords).rgb;
 
     vec3 ambient = light.ambient * diffuse_colours;
     vec3 diffuse = intensity * light.diffuse * diffuse_factor * diffuse_colours;
     vec3 specular = intensity * light.specular * specular_factor * specular_colours;
 
-    return attenuation * (ambient + diffuse + specular);
+    float shadow = compute_spot_shadow(frag_pos);
+
+    return attenuation * (ambient + (1.0 - shadow) * (diffuse + specular));
+}
+
+float compute_spot_shadow(vec3 frag_pos)
+{
+    vec4 frag_pos_light_space = uSpotLightSpace * vec4(frag_pos, 1.0);
+    if (frag_pos_light_space.w <= 0.0) {
+        return 0.0;
+    }
+    vec3 coords = (frag_pos_light_space.xyz / frag_pos_light_space.w) * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 0.0;
+    }
+    float receiver = linearise_spot_depth(coords.z);
+
+    // PCSS step 1: average the depth of occluders inside the light's footprint
+    float search_radius = uSpotLightSize * (receiver - uSpotShadowNear) / receiver;
+    float blocker_sum = 0.0;
+    int blocker_count = 0;
+    for (int i = 0; i != 16; ++i) {
+        vec2 offset = POISSON_DISK[i] * search_radius;
+        float depth = linearise_spot_depth(texture(uSpotShadowMap, vec3(coords.xy + offset, 0.0)).r);
+        if (depth < receiver - uSpotShadowBias) {
+            blocker_sum += depth;
+            blocker_count += 1;
+        }
+    }
+    if (blocker_count == 0) {
+        return 0.0;
+    }
+    float blocker = blocker_sum / float(blocker_count);
+
+    // PCSS step 2: the penumbra widens with the receiver's distance past the blockers
+    float penumbra = (receiver - blocker) * uSpotLightSize / blocker;
+    float min_radius = 1.0 / float(textureSize(uSpotShadowMap, 0).x);
+    float filter_radius = max(penumbra * uSpotShadowNear / receiver, min_radius);
+
+    // PCSS step 3: percentage-closer filtering over the estimated penumbra
+    float shadow = 0.0;
+    for (int i = 0; i != 16; ++i) {
+        vec2 offset = POISSON_DISK[i] * filter_radius;
+        float depth = linearise_spot_depth(texture(uSpotShadowMap, vec3(coords.xy + offset, 0.0)).r);
+        shadow += (receiver - uSpotShadowBias > depth) ? 1.0 : 0.0;
+    }
+    return shadow / 16.0;
+}
+
+float linearise_spot_depth(float depth)
+{
+    float z = depth * 2.0 - 1.0;
+    return (2.0 * uSpotShadowNear * uSpotShadowFar) / (uSpotShadowFar + uSpotShadowNear - z * (uSpotShadowFar - uSpotShadowNear));
 }
\ No newline at end of file
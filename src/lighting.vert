@@ -2,19 +2,28 @@
 layout (location = 0) in vec3 inVertices;
 layout (location = 1) in vec3 inNormals;
 layout (location = 2) in vec2 inTextureCoords;
+layout (location = 4) in mat4 inInstanceModel;
+layout (location = 8) in mat3 inInstanceNormal;
 
+uniform bool uInstanced;
 uniform mat4 uModel;
+uniform mat3 uNormalMatrix;
 uniform mat4 uView;
 uniform mat4 uProjection;
 
 out vec3 aNormal;
 out vec3 aFragPos;
 out vec2 aTextureCoords;
+out float aViewDepth;
 
 void main()
 {
-    aNormal = mat3(transpose(inverse(uModel))) * inNormals;
-    aFragPos = vec3(uModel * vec4(inVertices, 1.0));
+    mat4 model = uInstanced ? inInstanceModel : uModel;
+    mat3 normal_matrix = uInstanced ? inInstanceNormal : uNormalMatrix;
+
+    aNormal = normal_matrix * inNormals;
+    aFragPos = vec3(model * vec4(inVertices, 1.0));
     aTextureCoords = inTextureCoords;
-	gl_Position = uProjection * uView * uModel * vec4(inVertices, 1.0);
+    aViewDepth = -(uView * vec4(aFragPos, 1.0)).z;
+	gl_Position = uProjection * uView * model * vec4(inVertices, 1.0);
 }
\ No newline at end of file
@@ -1,18 +1,46 @@
 extern crate gl;
+mod bloom;
+mod bounds;
 mod camera;
+mod camera_path;
+mod fly;
+mod framebuffer;
+mod frustum;
+mod gamepad;
+mod gltf_import;
+mod input;
+mod light;
+mod material;
+mod mesh;
+mod model;
+mod obj;
+mod options;
+mod orbit;
+mod picking;
+mod postprocess;
+mod primitive;
+mod scene;
+mod scene_file;
 mod shader;
+mod shadow;
 mod texture;
+mod tonemap;
 
 use crate::camera::Camera;
 use camera::Direction;
 use gl::types::*;
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use input::Action;
+use postprocess::{Input, Uniform};
+use rand::{Rng, SeedableRng};
+use sdl2::event::{Event, WindowEvent};
 use sdl2::video::GLProfile;
 use sdl2::{EventPump, TimerSubsystem};
+use std::path::PathBuf;
+use std::rc::Rc;
 
 fn main() -> Result<(), String> {
+    let options = options::parse()?;
+
     let sdl = sdl2::init()?;
     let video_subsystem = sdl.video()?;
 
@@ -21,8 +49,8 @@ fn main() -> Result<(), String> {
     gl_attr.set_context_version(3, 3);
 
     sdl.mouse().set_relative_mouse_mode(true);
-    let window = video_subsystem
-        .window("Rust Renderer", 1200, 900)
+    let mut window = video_subsystem
+        .window(WINDOW_TITLE, 1200, 900)
         .opengl()
         .resizable()
         .build()
@@ -34,6 +62,7 @@ fn main() -> Result<(), String> {
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
     }
+    let clip_control = has_clip_control();
 
     #[rustfmt::skip]
     let vertices_cube: [f32; 288] = [
@@ -87,226 +116,700 @@ fn main() -> Result<(), String> {
         include_str!("light_cube.vert"),
         include_str!("light_cube.frag"),
     )?;
-    let texture_wood_steel_border = texture::create(include_bytes!("wood_steel_border.png"))?;
-    let texture_only_steel_border = texture::create(include_bytes!("steel_border.png"))?;
+    let shader_shadow_depth = shader::compile_from_sources(
+        include_str!("shadow_depth.vert"),
+        include_str!("shadow_depth.frag"),
+    )?;
+    let shader_point_shadow_depth = shader::compile_from_sources_with_geometry(
+        include_str!("point_shadow_depth.vert"),
+        include_str!("point_shadow_depth.geom"),
+        include_str!("point_shadow_depth.frag"),
+    )?;
 
-    let mut vao_cube: GLuint = 0;
-    let mut vbo: GLuint = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao_cube);
-        gl::GenBuffers(1, &mut vbo);
-
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (vertices_cube.len() * std::mem::size_of::<f32>())
-                .try_into()
-                .map_err(error_to_string())?,
-            vertices_cube.as_ptr() as *const std::os::raw::c_void,
-            gl::STATIC_DRAW,
+    let (mut window_width, mut window_height) = window.size();
+    let mut scene_framebuffer = framebuffer::create(
+        window_width,
+        window_height,
+        framebuffer::ColourFormat::Rgba16F,
+        true,
+    )?;
+    let mut id_buffer = picking::create_id_buffer(window_width, window_height)?;
+    let mut bloom = bloom::create(window_width, window_height, 6)?;
+    let mut post_processing = postprocess::create(window_width, window_height)?;
+    post_processing.add(
+        postprocess::pass("tonemap", include_str!("tonemap.frag"))?
+            .with_input("uImage", Input::Previous),
+    );
+    post_processing.add(
+        postprocess::pass("sharpen", include_str!("sharpen.frag"))?
+            .with_input("uImage", Input::Previous)
+            .with_uniform("uStrength", Uniform::Float(0.5))
+            .disabled(),
+    );
+    post_processing.add(
+        postprocess::pass("grayscale", include_str!("grayscale.frag"))?
+            .with_input("uImage", Input::Previous)
+            .disabled(),
+    );
+    post_processing.add(
+        postprocess::pass("vignette", include_str!("vignette.frag"))?
+            .with_input("uImage", Input::Previous)
+            .with_uniform("uRadius", Uniform::Float(0.75))
+            .with_uniform("uSoftness", Uniform::Float(0.45))
+            .with_uniform("uStrength", Uniform::Float(0.6)),
+    );
+    for path in &options.post_passes {
+        post_processing.add(postprocess::pass_from_file(path)?);
+    }
+    post_processing.add(
+        postprocess::pass("split", include_str!("split.frag"))?
+            .with_input("uScene", Input::Scene)
+            .with_input("uImage", Input::Previous)
+            .with_uniform("uSplit", Uniform::Float(0.5))
+            .disabled(),
+    );
+    for (index, pass) in post_processing.get_passes().iter().enumerate() {
+        println!(
+            "[{}] post-process '{}' ({})",
+            index + 1,
+            pass.get_name(),
+            if pass.is_enabled() { "on" } else { "off" }
         );
+    }
 
-        gl::BindVertexArray(vao_cube);
-        gl::VertexAttribPointer(
-            0,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            (8 * std::mem::size_of::<f32>()) as i32,
-            std::ptr::null(),
-        );
-        gl::EnableVertexAttribArray(0);
-        gl::VertexAttribPointer(
-            1,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            (8 * std::mem::size_of::<f32>()) as i32,
-            (3 * std::mem::size_of::<f32>()) as *const std::os::raw::c_void,
-        );
-        gl::EnableVertexAttribArray(1);
-        gl::VertexAttribPointer(
-            2,
-            2,
-            gl::FLOAT,
-            gl::FALSE,
-            (8 * std::mem::size_of::<f32>()) as i32,
-            (6 * std::mem::size_of::<f32>()) as *const std::os::raw::c_void,
+    let (cube_vertices, cube_indices) = mesh::deduplicate(
+        &vertices_cube
+            .chunks(8)
+            .map(|v| mesh::StandardVertex {
+                position: [v[0], v[1], v[2]],
+                normal: [v[3], v[4], v[5]],
+                texture_coords: [v[6], v[7]],
+            })
+            .collect::<Vec<_>>(),
+    );
+    let mut cube = mesh::create(&cube_vertices, Some(&cube_indices))?;
+    // Cubes placed by the scene file share this mesh, the field's one carries instances
+    let scene_cube = Rc::new(mesh::create(&cube_vertices, Some(&cube_indices))?);
+
+    let mut scene = scene::create();
+
+    if let Some(path) = &options.model {
+        let model = obj::load(path)?;
+        let root = scene.add_node(
+            &path.display().to_string(),
+            None,
+            fit_to_size(&model, MODEL_SIZE),
         );
-        gl::EnableVertexAttribArray(2);
+        for part in model.get_parts() {
+            let node = scene.add_node(&part.name, Some(root), scene::identity());
+            scene.add_component(
+                node,
+                scene::Component::Renderable {
+                    mesh: part.mesh.clone(),
+                    material: part.material.clone(),
+                },
+            );
+        }
+        println!("Loaded:");
+        scene.print_hierarchy(root);
     }
-    let vao_cube = vao_cube;
-    let vbo = vbo;
-
-    let projection = nalgebra_glm::perspective(
-        window.size().0 as f32 / window.size().1 as f32,
-        num::Float::to_radians(45.0),
-        0.1,
-        100.0,
-    );
 
-    let mut camera = camera::start_from_world_pos(nalgebra_glm::vec3(0.0, 0.0, 3.0));
-    let mut current_movement: [Option<Direction>; 6] = [None, None, None, None, None, None];
-    let mut flashlight_state = true;
-
-    let cube_radius: f32 = 10.0;
-    let mut rng = rand::thread_rng();
-    let mut create_random_vector = || {
-        nalgebra_glm::vec3(
-            cube_radius * rng.gen::<f32>() - (cube_radius / 2.0),
-            cube_radius * rng.gen::<f32>() - (cube_radius / 2.0),
-            cube_radius * rng.gen::<f32>() - (cube_radius / 2.0),
-        )
+    if let Some(path) = &options.gltf {
+        let root = gltf_import::load(path, &mut scene, None)?;
+        println!("Loaded:");
+        scene.print_hierarchy(root);
+    }
+
+    let mut description = match (&options.scene, &options.gltf) {
+        (Some(path), _) => scene_file::load(path)?,
+        (None, Some(_)) => scene_file::empty(),
+        (None, None) => scene_file::default_scene()?,
     };
+    if let Some(count) = options.cube_count {
+        description.set_cube_count(count);
+    }
+    // The same seed reproduces the same cube positions, spin axes and lights
+    let seed = options
+        .seed
+        .or(description.seed)
+        .unwrap_or_else(|| rand::thread_rng().gen());
+    println!("Seed: {} (rerun with --seed {} to reproduce)", seed, seed);
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
+    let scene_file::Setup {
+        camera: start_camera,
+        movement,
+        flashlight,
+        cube_nodes,
+        cube_material,
+        spins,
+    } = scene_file::build(&description, &mut scene, &scene_cube, &mut rng)?;
+    if scene.point_lights().len() > light::NUM_POINT_LIGHTS {
+        println!(
+            "Using {} of the scene's {} point lights",
+            light::NUM_POINT_LIGHTS,
+            scene.point_lights().len()
+        );
+    }
+    scene.update();
 
-    let cube_positions: Vec<(nalgebra_glm::Vec3, nalgebra_glm::Vec3)> =
-        std::iter::repeat_with(|| (create_random_vector(), create_random_vector()))
-            .take(50)
-            .collect();
+    // A camera from an imported file takes over, keeping the scene's depth range
+    let mut camera = match scene.cameras().into_iter().next() {
+        Some(scene_camera) => {
+            let projection = start_camera.get_projection();
+            camera::look_from(
+                scene_camera.position,
+                &scene_camera.front,
+                match scene_camera.y_fov {
+                    Some(y_fov) => projection.with_y_fov(y_fov),
+                    None => projection,
+                },
+            )
+        }
+        None => start_camera,
+    };
+    camera.set_aspect(window_width as f32 / window_height as f32);
+    let (path, playback) = match &options.camera_path {
+        Some(file) if file.exists() => {
+            let path = camera_path::load(file)?;
+            println!("Playing camera path {} on a loop", file.display());
+            (path, Some(camera_path::play(true)))
+        }
+        _ => (camera_path::create(), None),
+    };
+    let mut viewer = Viewer {
+        camera,
+        input: input::load(options.bindings.as_deref())?,
+        gamepads: gamepad::create(sdl.game_controller()?),
+        cursor: (0, 0),
+        fly: fly::create(movement),
+        orbit: None,
+        path,
+        path_file: options
+            .camera_path
+            .clone()
+            .unwrap_or(PathBuf::from(CAMERA_PATH_FILE)),
+        playback,
+        loop_playback: true,
+        cursor_free: false,
+    };
+    let mut settings = Settings {
+        flashlight: true,
+        instanced: true,
+        culling: true,
+        gpu_picking: false,
+        tone_mapping: tonemap::create(tonemap::Operator::Aces, 1.0),
+        bloom: bloom::settings(1.0, 0.6),
+    };
 
-    let point_light_positions: Vec<nalgebra_glm::Vec3> =
-        std::iter::repeat_with(create_random_vector)
-            .take(4)
-            .collect();
+    // Unused slots in the shader's fixed array get a black light so they stay valid
+    let unused_point_light = light::point(
+        nalgebra_glm::Vec3::zeros(),
+        nalgebra_glm::Vec3::zeros(),
+        false,
+    );
 
+    // Used when the scene has no directional light of its own
+    let unlit_directional_light = light::DirectionalLight {
+        direction: nalgebra_glm::vec3(0.0, -1.0, 0.0),
+        ambient: nalgebra_glm::Vec3::zeros(),
+        diffuse: nalgebra_glm::Vec3::zeros(),
+        specular: nalgebra_glm::Vec3::zeros(),
+    };
+    let shadow_settings = shadow::Settings {
+        resolution: 2048,
+        cascade_count: 4,
+        split_lambda: 0.75,
+        max_distance: 60.0,
+        caster_extension: 20.0,
+        blend_fraction: 0.1,
+        pcf_radius: 1,
+        bias_constant: 0.0005,
+        bias_slope: 0.002,
+    };
+    let shadow_map = shadow::create(
+        shadow_settings.resolution,
+        shadow_settings.cascade_count as u32,
+    )?;
+    let point_shadow_settings = shadow::PointSettings {
+        resolution: 1024,
+        near: 0.1,
+        far: 25.0,
+    };
+    let point_shadow_maps = active_point_lights(&scene)
+        .iter()
+        .map(|point_light| {
+            if point_light.casts_shadows {
+                shadow::create_cube(point_shadow_settings.resolution).map(Some)
+            } else {
+                Ok(None)
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let spot_shadow_settings = shadow::SpotSettings {
+        resolution: 1024,
+        near: 0.1,
+        far: 50.0,
+        light_size: 0.05,
+        bias: 0.05,
+    };
+    let spot_shadow_map = shadow::create(spot_shadow_settings.resolution, 1)?;
     let mut event_pump = sdl.event_pump()?;
     let timer = sdl.timer()?;
     let mut last_ticks = timer.performance_counter() as f64;
+    // Shown in the window title whenever they change
+    let mut last_counts = frustum::Counts::default();
+    let mut selected: Option<scene::NodeId> = None;
+    // The cube instances are uploaded again only once the cubes have moved,
+    // and the visible subset only when it changes
+    let mut instances_stale = true;
+    let mut instance_subset: Option<Vec<scene::NodeId>> = None;
     loop {
-        let seconds = match process_events(
+        let Frame {
+            seconds,
+            delta_seconds,
+            resized,
+            frame_scene,
+            pick,
+        } = match process_events(
             &mut event_pump,
             &timer,
             &mut last_ticks,
-            &mut camera,
-            &mut current_movement,
-            &mut flashlight_state,
+            &mut viewer,
+            &mut settings,
+            &mut post_processing,
         ) {
             Some(val) => val,
             None => break,
         };
 
+        // Everything sized to the window is rebuilt, the default framebuffer's
+        // viewport follows from the new size when post-processing binds it
+        if let Some((width, height)) = resized {
+            (window_width, window_height) = (width, height);
+            viewer.camera.set_aspect(width as f32 / height as f32);
+            scene_framebuffer.resize(width, height)?;
+            id_buffer.resize(width, height)?;
+            bloom.resize(width, height)?;
+            post_processing.resize(width, height)?;
+        }
+
+        if frame_scene {
+            if let Some(bounds) = visible_bounds(&scene, &cube, &cube_nodes) {
+                viewer
+                    .orbit
+                    .get_or_insert_with(|| orbit::around(&viewer.camera, ORBIT_DISTANCE))
+                    .frame(&bounds, &mut viewer.camera);
+            }
+        }
+        if let Some(orbit) = &viewer.orbit {
+            orbit.apply(&mut viewer.camera);
+        }
+        let camera = &viewer.camera;
+
+        for spin in &spins {
+            spin.apply(&mut scene, seconds);
+        }
+        scene.update();
+        instances_stale |= cube_nodes.iter().any(|&node| scene.was_moved(node));
+
+        let directional_light = scene
+            .directional_lights()
+            .into_iter()
+            .next()
+            .unwrap_or(unlit_directional_light.clone());
+        let point_lights = active_point_lights(&scene);
+        // A spot light from the scene stays where it was placed, otherwise it is a flashlight
+        let flashlight = scene
+            .spot_lights()
+            .into_iter()
+            .next()
+            .unwrap_or(light::SpotLight {
+                position: camera.get_position(),
+                direction: camera.get_front(),
+                ..flashlight.clone()
+            });
+
+        let cascades = shadow_settings.fit_cascades(
+            &directional_light.direction,
+            &shadow::ViewFrustum {
+                view: camera.get_view_matrix(),
+                projection: camera.get_projection(),
+                aspect: camera.get_aspect(),
+            },
+        );
+
+        let spot_light_space = spot_shadow_settings.light_space_matrix(&flashlight);
+
+        if settings.instanced && instances_stale {
+            cube.set_instances(&instance_transforms(&scene, &cube_nodes))?;
+            instances_stale = false;
+            instance_subset = None;
+        }
+        let all_instances = settings.instanced.then_some(mesh::Instances::All);
+
+        // Shadow pass
+        shader_shadow_depth.enable();
+        for (layer, light_space) in cascades.light_spaces.iter().enumerate() {
+            shadow_map.bind_layer(layer as u32)?;
+            shader_shadow_depth.set_mat4("uLightSpace", light_space)?;
+            draw_cubes(
+                &shader_shadow_depth,
+                &cube,
+                &scene,
+                &cube_nodes,
+                all_instances,
+                false,
+            )?;
+            scene.draw(&shader_shadow_depth, false, None)?;
+        }
+
+        if settings.flashlight {
+            spot_shadow_map.bind_layer(0)?;
+            shader_shadow_depth.set_mat4("uLightSpace", &spot_light_space)?;
+            draw_cubes(
+                &shader_shadow_depth,
+                &cube,
+                &scene,
+                &cube_nodes,
+                all_instances,
+                false,
+            )?;
+            scene.draw(&shader_shadow_depth, false, None)?;
+        }
+
+        shader_point_shadow_depth.enable();
+        for (point_light, shadow_map) in point_lights.iter().zip(&point_shadow_maps) {
+            if let Some(shadow_map) = shadow_map {
+                shadow_map.bind()?;
+                point_shadow_settings
+                    .apply_depth(&shader_point_shadow_depth, &point_light.position)?;
+                draw_cubes(
+                    &shader_point_shadow_depth,
+                    &cube,
+                    &scene,
+                    &cube_nodes,
+                    all_instances,
+                    false,
+                )?;
+                scene.draw(&shader_point_shadow_depth, false, None)?;
+            }
+        }
+
+        // Only the camera's pass is culled, things out of view still cast shadows into it
+        let frustum = settings.culling.then(|| camera.get_frustum());
+        let visible_cubes = cubes_in_view(&scene, &cube, &cube_nodes, frustum.as_ref());
+        let cube_instances = if visible_cubes.len() < cube_nodes.len() {
+            if settings.instanced && instance_subset.as_ref() != Some(&visible_cubes) {
+                cube.set_instance_subset(&instance_transforms(&scene, &visible_cubes))?;
+                instance_subset = Some(visible_cubes.clone());
+            }
+            settings.instanced.then_some(mesh::Instances::Subset)
+        } else {
+            all_instances
+        };
+        let mut counts = frustum::Counts {
+            drawn: visible_cubes.len(),
+            culled: cube_nodes.len() - visible_cubes.len(),
+        };
+
+        let mouse = sdl.mouse();
+        if mouse.relative_mouse_mode() == viewer.cursor_free {
+            mouse.set_relative_mouse_mode(!viewer.cursor_free);
+        }
+
+        let targets = pick_targets(&scene, &cube, &cube_nodes);
+        set_reversed_z(camera.get_projection().is_reversed_z(), clip_control);
+        if let Some((x, y)) = pick {
+            selected = if settings.gpu_picking {
+                id_buffer.pick(camera, &targets, &scene, x, y)?
+            } else {
+                let ray = picking::cursor_ray(camera, x, y, window_width, window_height);
+                picking::pick(&ray, &targets, &scene)
+            };
+            match selected {
+                Some(node) => report_selection(&scene, node),
+                None => println!("Nothing selected"),
+            }
+        }
+
+        scene_framebuffer.bind()?;
         unsafe {
             gl::ClearColor(0.1, 0.1, 0.1, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
             shader_lighting.enable();
 
-            shader_lighting.set_int("uMaterial.diffuse", 0)?;
-            shader_lighting.set_int("uMaterial.specular", 1)?;
-
-            shader_lighting.set_mat4("uProjection", &projection)?;
+            shader_lighting.set_mat4("uProjection", &camera.get_projection_matrix())?;
             shader_lighting.set_mat4("uView", &camera.get_view_matrix())?;
             shader_lighting.set_vec3("uViewPos", &camera.get_position())?;
-            shader_lighting.set_float("uMaterial.shininess", 32.0)?;
 
             // Directional Lighting
-            shader_lighting.set_vec3(
-                "uDirectionalLight.direction",
-                &nalgebra_glm::vec3(-0.2, -1.0, -0.3),
-            )?;
-            shader_lighting.set_vec3(
-                "uDirectionalLight.ambient",
-                &nalgebra_glm::vec3(0.05, 0.05, 0.05),
-            )?;
-            shader_lighting.set_vec3(
-                "uDirectionalLight.diffuse",
-                &nalgebra_glm::vec3(0.4, 0.4, 0.4),
-            )?;
-            shader_lighting.set_vec3(
-                "uDirectionalLight.specular",
-                &nalgebra_glm::vec3(0.5, 0.5, 0.5),
-            )?;
+            directional_light.apply(&shader_lighting)?;
+            shader_lighting.set_int("uShadowMap", 2)?;
+            shadow_settings.apply(&shader_lighting, &cascades)?;
 
             // Spot Lighting
-            shader_lighting.set_vec3("uSpotLight.position", &camera.get_position())?;
-            shader_lighting.set_vec3("uSpotLight.direction", &camera.get_front())?;
-            shader_lighting.set_float(
-                "uSpotLight.inner_cutoff",
-                num::Float::to_radians(12.5 as f32).cos(),
-            )?;
-            shader_lighting.set_float(
-                "uSpotLight.outer_cutoff",
-                num::Float::to_radians(17.5 as f32).cos(),
-            )?;
-            shader_lighting.set_float("uSpotLight.attenuation_constant", 1.0)?;
-            shader_lighting.set_float("uSpotLight.attenuation_linear", 0.07)?;
-            shader_lighting.set_float("uSpotLight.attenuation_quadratic", 0.017)?;
-            shader_lighting.set_vec3("uSpotLight.ambient", &nalgebra_glm::vec3(0.1, 0.1, 0.1))?;
-            shader_lighting.set_vec3("uSpotLight.diffuse", &nalgebra_glm::vec3(1.0, 1.0, 1.0))?;
-            shader_lighting.set_vec3("uSpotLight.specular", &nalgebra_glm::vec3(2.0, 2.0, 2.0))?;
-            shader_lighting.set_int("uFlashlight", flashlight_state as i32)?;
+            flashlight.apply(&shader_lighting)?;
+            shader_lighting.set_int("uFlashlight", settings.flashlight as i32)?;
+            shader_lighting.set_int("uSpotShadowMap", SPOT_SHADOW_UNIT)?;
+            spot_shadow_settings.apply(&shader_lighting, &spot_light_space)?;
 
             // Point Lighting
-            for (i, position) in point_light_positions.iter().enumerate() {
-                shader_lighting
-                    .set_vec3(format!("uPointLights[{}].position", i).as_str(), position)?;
-                shader_lighting.set_vec3(
-                    format!("uPointLights[{}].ambient", i).as_str(),
-                    &nalgebra_glm::vec3(0.05, 0.05, 0.05),
-                )?;
-                shader_lighting.set_vec3(
-                    format!("uPointLights[{}].diffuse", i).as_str(),
-                    &nalgebra_glm::vec3(0.8, 0.8, 0.8),
-                )?;
-                shader_lighting.set_vec3(
-                    format!("uPointLights[{}].specular", i).as_str(),
-                    &nalgebra_glm::vec3(1.0, 1.0, 1.0),
-                )?;
-                shader_lighting.set_float(
-                    format!("uPointLights[{}].attenuation_constant", i).as_str(),
-                    1.0,
-                )?;
-                shader_lighting.set_float(
-                    format!("uPointLights[{}].attenuation_linear", i).as_str(),
-                    0.09,
-                )?;
-                shader_lighting.set_float(
-                    format!("uPointLights[{}].attenuation_quadratic", i).as_str(),
-                    0.032,
-                )?;
+            for i in 0..light::NUM_POINT_LIGHTS {
+                point_lights
+                    .get(i)
+                    .unwrap_or(&unused_point_light)
+                    .apply(&shader_lighting, i)?;
             }
+            point_shadow_settings.apply(&shader_lighting, POINT_SHADOW_FIRST_UNIT)?;
 
+            gl::ActiveTexture(gl::TEXTURE2);
+            shadow_map.bind_depth_texture();
+            for (i, shadow_map) in point_shadow_maps.iter().enumerate() {
+                if let Some(shadow_map) = shadow_map {
+                    gl::ActiveTexture(
+                        gl::TEXTURE0 + (POINT_SHADOW_FIRST_UNIT as GLuint) + i as GLuint,
+                    );
+                    shadow_map.bind_depth_texture();
+                }
+            }
+            gl::ActiveTexture(gl::TEXTURE0 + SPOT_SHADOW_UNIT as GLuint);
+            spot_shadow_map.bind_depth_texture();
             gl::ActiveTexture(gl::TEXTURE0);
-            texture_wood_steel_border.bind();
-            gl::ActiveTexture(gl::TEXTURE1);
-            texture_only_steel_border.bind();
 
-            for (position, axis) in &cube_positions {
-                let model = nalgebra_glm::rotate(
-                    &nalgebra_glm::translate(&nalgebra_glm::one(), &position),
-                    seconds,
-                    &axis,
-                );
-
-                shader_lighting.set_mat4("uModel", &model)?;
-
-                gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            if let Some(cube_material) = &cube_material {
+                cube_material.apply(&shader_lighting)?;
+                draw_cubes(
+                    &shader_lighting,
+                    &cube,
+                    &scene,
+                    &visible_cubes,
+                    cube_instances,
+                    true,
+                )?;
             }
+            counts = counts + scene.draw(&shader_lighting, true, frustum.as_ref())?;
 
             shader_light_cube.enable();
 
-            shader_light_cube.set_mat4("uProjection", &projection)?;
-            shader_light_cube.set_mat4("uView", &camera.get_view_matrix())?;
-            for position in &point_light_positions {
-                let model = nalgebra_glm::translate(&nalgebra_glm::one(), &position);
+            shader_light_cube.set_mat4("uViewProjection", &camera.get_view_projection())?;
+            shader_light_cube.set_float("uIntensity", LIGHT_CUBE_INTENSITY)?;
+            for point_light in &point_lights {
+                let model = nalgebra_glm::translate(&nalgebra_glm::one(), &point_light.position);
                 shader_light_cube.set_mat4("uModel", &model)?;
-                gl::DrawArrays(gl::TRIANGLES, 0, 36);
+                shader_light_cube.set_vec3("uColour", &point_light.colour)?;
+                cube.draw();
+            }
+
+            // A wireframe drawn over everything, so it shows through whatever is in front
+            if let Some(target) = targets.iter().find(|target| Some(target.node) == selected) {
+                shader_light_cube.set_mat4("uModel", scene.get_world_transform(target.node))?;
+                shader_light_cube.set_vec3("uColour", &nalgebra_glm::vec3(1.0, 0.8, 0.2))?;
+                gl::Disable(gl::DEPTH_TEST);
+                gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                target.mesh.draw();
+                gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                gl::Enable(gl::DEPTH_TEST);
             }
+        }
+
+        set_reversed_z(false, clip_control);
+
+        bloom.apply(&scene_framebuffer, &settings.bloom)?;
 
+        if settings.tone_mapping.is_auto_exposure() {
+            settings
+                .tone_mapping
+                .adapt(&scene_framebuffer.average_colour(), delta_seconds);
+        }
+        if let Some(pass) = post_processing.get_pass_mut("tonemap") {
+            settings.tone_mapping.apply(pass);
+        }
+        post_processing.run(&scene_framebuffer, window_width, window_height)?;
+        unsafe {
             assert_eq!(gl::GetError(), 0);
         }
 
+        if counts != last_counts {
+            window
+                .set_title(&format!(
+                    "{} - {} drawn, {} culled",
+                    WINDOW_TITLE, counts.drawn, counts.culled
+                ))
+                .map_err(error_to_string())?;
+            last_counts = counts;
+        }
+
         window.gl_swap_window();
     }
 
-    unsafe {
-        gl::DeleteVertexArrays(1, &vao_cube as *const GLuint);
-        gl::DeleteBuffers(1, &vbo as *const GLuint);
+    Ok(())
+}
+
+// The cubes and everything the scene draws
+fn pick_targets<'a>(
+    scene: &'a scene::Scene,
+    cube: &'a mesh::Mesh,
+    cube_nodes: &[scene::NodeId],
+) -> Vec<picking::Target<'a>> {
+    cube_nodes
+        .iter()
+        .map(|&node| picking::Target { node, mesh: cube })
+        .chain(
+            scene
+                .renderables()
+                .into_iter()
+                .map(|(node, mesh)| picking::Target { node, mesh }),
+        )
+        .collect()
+}
+
+fn report_selection(scene: &scene::Scene, node: scene::NodeId) {
+    let format = |v: &nalgebra_glm::Vec3| format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z);
+    let local = scene.get_local_transform(node);
+    let world = scene.get_world_transform(node);
+    println!(
+        "Selected {}: translation {}, rotation {:.1} degrees about {}, scale {}, world position {}",
+        scene.get_name(node),
+        format(&local.translation),
+        nalgebra_glm::quat_angle(&local.rotation).to_degrees(),
+        format(&nalgebra_glm::quat_axis(&local.rotation)),
+        format(&local.scale),
+        format(&world.column(3).xyz()),
+    );
+}
+
+fn instance_transforms(
+    scene: &scene::Scene,
+    cube_nodes: &[scene::NodeId],
+) -> Vec<mesh::InstanceTransform> {
+    cube_nodes
+        .iter()
+        .map(|node| mesh::instance_transform(scene.get_world_transform(*node)))
+        .collect()
+}
+
+// All of the cubes without a frustum to test against
+fn cubes_in_view(
+    scene: &scene::Scene,
+    cube: &mesh::Mesh,
+    cube_nodes: &[scene::NodeId],
+    frustum: Option<&frustum::Frustum>,
+) -> Vec<scene::NodeId> {
+    match (frustum, cube.get_bounds()) {
+        (Some(frustum), Some(bounds)) => cube_nodes
+            .iter()
+            .copied()
+            .filter(|node| frustum.intersects(&bounds.transform(scene.get_world_transform(*node))))
+            .collect(),
+        _ => cube_nodes.to_vec(),
+    }
+}
+
+fn draw_cubes(
+    shader: &shader::Shader,
+    cube: &mesh::Mesh,
+    scene: &scene::Scene,
+    cube_nodes: &[scene::NodeId],
+    instances: Option<mesh::Instances>,
+    with_normals: bool,
+) -> Result<(), String> {
+    if let Some(instances) = instances {
+        shader.set_int("uInstanced", 1)?;
+        cube.draw_instances(instances);
+        shader.set_int("uInstanced", 0)?;
+        return Ok(());
     }
 
+    for node in cube_nodes {
+        let model = scene.get_world_transform(*node);
+        shader.set_mat4("uModel", model)?;
+        if with_normals {
+            shader.set_mat3("uNormalMatrix", &mesh::normal_matrix(model))?;
+        }
+        cube.draw();
+    }
     Ok(())
 }
 
+// Reversed-Z clears depth to 0 and keeps nearer fragments with a GREATER test.
+// Clip control, where available, keeps the [0, 1] depth range at full precision.
+fn set_reversed_z(reversed: bool, clip_control: bool) {
+    unsafe {
+        if clip_control {
+            gl::ClipControl(
+                gl::LOWER_LEFT,
+                if reversed {
+                    gl::ZERO_TO_ONE
+                } else {
+                    gl::NEGATIVE_ONE_TO_ONE
+                },
+            );
+        }
+        gl::ClearDepth(if reversed { 0.0 } else { 1.0 });
+        gl::DepthFunc(if reversed { gl::GREATER } else { gl::LESS });
+    }
+}
+
+// A resolved function pointer doesn't mean the context accepts the call, so
+// this also asks for GL 4.5 or the extension
+fn has_clip_control() -> bool {
+    let (mut major, mut minor, mut extensions) = (0, 0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extensions);
+    }
+    gl::ClipControl::is_loaded()
+        && ((major, minor) >= (4, 5)
+            || (0..extensions as GLuint).any(|i| {
+                let name = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+                !name.is_null()
+                    && unsafe { std::ffi::CStr::from_ptr(name as *const std::os::raw::c_char) }
+                        .to_bytes()
+                        == b"GL_ARB_clip_control"
+            }))
+}
+
+// The scene's renderables and the cube field, which is drawn outside the scene
+fn visible_bounds(
+    scene: &scene::Scene,
+    cube: &mesh::Mesh,
+    cube_nodes: &[scene::NodeId],
+) -> Option<bounds::Bounds> {
+    let cube_bounds = cube.get_bounds()?;
+    cube_nodes
+        .iter()
+        .map(|node| cube_bounds.transform(scene.get_world_transform(*node)))
+        .chain(scene.get_bounds())
+        .reduce(|total, bounds| total.union(&bounds))
+}
+
+fn active_point_lights(scene: &scene::Scene) -> Vec<light::PointLight> {
+    scene
+        .point_lights()
+        .into_iter()
+        .take(light::NUM_POINT_LIGHTS)
+        .enumerate()
+        .map(|(i, mut point_light)| {
+            point_light.casts_shadows = i < SHADOWED_POINT_LIGHTS;
+            point_light
+        })
+        .collect()
+}
+
+// Centres the model on the origin and scales its largest extent to `size`
+fn fit_to_size(model: &model::Model, size: f32) -> scene::Transform {
+    let (min, max) = model.get_bounds();
+    let extent = (max - min).max();
+    let scale = if extent > 0.0 { size / extent } else { 1.0 };
+    scene::Transform {
+        translation: -(min + max) * 0.5 * scale,
+        rotation: nalgebra_glm::quat_identity(),
+        scale: nalgebra_glm::vec3(scale, scale, scale),
+    }
+}
+
 fn error_to_string<E>() -> fn(E) -> String
 where
     E: std::fmt::Display,
@@ -314,40 +817,234 @@ where
     |e: E| e.to_string()
 }
 
-const CAMERA_SPEED: f32 = 10.0;
+struct Frame {
+    seconds: f32,
+    delta_seconds: f32,
+    // The window's new size when it changed since the last frame
+    resized: Option<(u32, u32)>,
+    // Whether to orbit the whole scene with it all in view
+    frame_scene: bool,
+    // Where the window was clicked with the cursor free, from the top left
+    pick: Option<(i32, i32)>,
+}
+
+struct Viewer {
+    camera: Camera,
+    input: input::InputMap,
+    gamepads: gamepad::Gamepads,
+    // The free cursor's position in the window, from the top left
+    cursor: (i32, i32),
+    fly: fly::Fly,
+    // Some while the orbit controller drives the camera instead of fly movement
+    orbit: Option<orbit::Orbit>,
+    path: camera_path::CameraPath,
+    // Where the path is saved to and loaded from
+    path_file: PathBuf,
+    // Some while the path drives the camera, taking over from everything else
+    playback: Option<camera_path::Playback>,
+    loop_playback: bool,
+    // A visible cursor for picking, instead of mouse look
+    cursor_free: bool,
+}
+
+struct Settings {
+    flashlight: bool,
+    instanced: bool,
+    culling: bool,
+    // Picks with the ID buffer rather than rays against bounds
+    gpu_picking: bool,
+    tone_mapping: tonemap::Settings,
+    bloom: bloom::Settings,
+}
+
+const WINDOW_TITLE: &str = "Rust Renderer";
+const LIGHT_CUBE_INTENSITY: f32 = 4.0;
+const MODEL_SIZE: f32 = 4.0;
+const SHADOWED_POINT_LIGHTS: usize = 2;
+const POINT_SHADOW_FIRST_UNIT: i32 = 3;
+const SPOT_SHADOW_UNIT: i32 = POINT_SHADOW_FIRST_UNIT + light::NUM_POINT_LIGHTS as i32;
+
+const ORBIT_DISTANCE: f32 = 5.0;
+const CAMERA_PATH_FILE: &str = "camera_path.ron";
+const MOVEMENT: [(Action, Direction); 6] = [
+    (Action::MoveForward, Direction::Forward),
+    (Action::MoveBackward, Direction::Backward),
+    (Action::MoveLeft, Direction::Left),
+    (Action::MoveRight, Direction::Right),
+    (Action::MoveUp, Direction::Up),
+    (Action::MoveDown, Direction::Down),
+];
+// Degrees per second, only the free camera rolls
+const ROLL_SPEED: f32 = 90.0;
 const CAMERA_SENSITIVITY: f32 = 0.2;
+// Degrees per second at full deflection of the right stick
+const GAMEPAD_LOOK_SPEED: f32 = 120.0;
 fn process_events(
     event_pump: &mut EventPump,
     timer: &TimerSubsystem,
     last_ticks: &mut f64,
-    camera: &mut Camera,
-    current_movement: &mut [Option<Direction>; 6],
-    flashlight_state: &mut bool,
-) -> Option<f32> {
+    viewer: &mut Viewer,
+    settings: &mut Settings,
+    post_processing: &mut postprocess::Pipeline,
+) -> Option<Frame> {
+    let Viewer {
+        camera,
+        input,
+        gamepads,
+        cursor,
+        fly,
+        orbit,
+        path,
+        path_file,
+        playback,
+        loop_playback,
+        cursor_free,
+    } = viewer;
+    let mut resized = None;
+    let mut frame_scene = false;
+    let mut pick = None;
     for event in event_pump.poll_iter() {
         match event {
             Event::Quit { .. } => return None,
-            Event::KeyDown { keycode, .. } => match keycode {
-                Some(Keycode::A) => current_movement[0] = Some(Direction::Left),
-                Some(Keycode::D) => current_movement[1] = Some(Direction::Right),
-                Some(Keycode::W) => current_movement[2] = Some(Direction::Forward),
-                Some(Keycode::S) => current_movement[3] = Some(Direction::Backward),
-                Some(Keycode::Space) => current_movement[4] = Some(Direction::Up),
-                Some(Keycode::LAlt) => current_movement[5] = Some(Direction::Down),
-                Some(Keycode::F) => *flashlight_state = !*flashlight_state,
-                _ => (),
-            },
-            Event::KeyUp { keycode, .. } => match keycode {
-                Some(Keycode::A) => current_movement[0] = None,
-                Some(Keycode::D) => current_movement[1] = None,
-                Some(Keycode::W) => current_movement[2] = None,
-                Some(Keycode::S) => current_movement[3] = None,
-                Some(Keycode::Space) => current_movement[4] = None,
-                Some(Keycode::LAlt) => current_movement[5] = None,
-                _ => (),
+            Event::MouseWheel { y, .. } => match orbit {
+                Some(orbit) => orbit.zoom(y as f32),
+                None => println!("Camera speed: {:.1}", fly.adjust_speed(y as f32)),
             },
+            // Minimising reports a zero size, which no framebuffer can have
+            Event::Window {
+                win_event: WindowEvent::SizeChanged(width, height),
+                ..
+            } if width > 0 && height > 0 => resized = Some((width as u32, height as u32)),
+            // Where the free cursor is, for picking
+            Event::MouseMotion { x, y, .. } | Event::MouseButtonDown { x, y, .. } => {
+                *cursor = (x, y)
+            }
             _ => (),
         }
+        gamepads.handle(&event);
+        for action in input.handle(&event) {
+            match action {
+                Action::Select if *cursor_free => pick = Some(*cursor),
+                Action::ToggleFreeCamera => {
+                    camera.toggle_free();
+                    println!(
+                        "Camera orientation: {}",
+                        if camera.is_free() { "free" } else { "euler" }
+                    );
+                }
+                Action::ToggleFlashlight => settings.flashlight = !settings.flashlight,
+                Action::CycleToneMapping => settings.tone_mapping.cycle_operator(),
+                Action::ToggleAutoExposure => settings.tone_mapping.toggle_auto_exposure(),
+                Action::IncreaseExposure => settings.tone_mapping.scale_exposure(1.25),
+                Action::DecreaseExposure => settings.tone_mapping.scale_exposure(0.8),
+                Action::ToggleBloom => settings.bloom.toggle(),
+                Action::ToggleOrbit => {
+                    *orbit = match orbit {
+                        Some(_) => None,
+                        None => Some(orbit::around(camera, ORBIT_DISTANCE)),
+                    };
+                    fly.stop();
+                    *playback = None;
+                    println!("Camera: {}", if orbit.is_some() { "orbit" } else { "fly" });
+                }
+                Action::FrameScene => frame_scene = true,
+                Action::RecordKeyframe => {
+                    path.add(camera.get_position(), camera.get_orientation());
+                    println!("Recorded camera keyframe {}", path.len());
+                }
+                Action::TogglePlayback => {
+                    if playback.take().is_some() {
+                        println!("Camera path stopped");
+                    } else if path.len() > 0 {
+                        *playback = Some(camera_path::play(*loop_playback));
+                        *orbit = None;
+                        fly.stop();
+                        println!(
+                            "Playing {} keyframes over {:.1}s",
+                            path.len(),
+                            path.duration()
+                        );
+                    }
+                }
+                Action::ToggleLooping => {
+                    *loop_playback = !*loop_playback;
+                    println!(
+                        "Camera path looping: {}",
+                        if *loop_playback { "on" } else { "off" }
+                    );
+                }
+                Action::ClearPath => {
+                    path.clear();
+                    *playback = None;
+                    println!("Camera path cleared");
+                }
+                Action::SavePath => match path.save(path_file) {
+                    Ok(()) => println!("Saved camera path to {}", path_file.display()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                Action::LoadPath => match camera_path::load(path_file) {
+                    Ok(loaded) => {
+                        *path = loaded;
+                        println!("Loaded camera path from {}", path_file.display());
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                Action::ToggleProjection => {
+                    camera.toggle_projection();
+                    println!(
+                        "Projection: {}",
+                        match camera.get_projection() {
+                            camera::Projection::Perspective { .. } => "perspective",
+                            camera::Projection::Orthographic { .. } => "orthographic",
+                            camera::Projection::ReversedInfinite { .. } => "reversed-Z infinite",
+                        }
+                    );
+                }
+                Action::ToggleInstancing => {
+                    settings.instanced = !settings.instanced;
+                    println!(
+                        "Instanced cubes: {}",
+                        if settings.instanced { "on" } else { "off" }
+                    );
+                }
+                Action::ToggleCulling => {
+                    settings.culling = !settings.culling;
+                    println!(
+                        "Frustum culling: {}",
+                        if settings.culling { "on" } else { "off" }
+                    );
+                }
+                Action::ToggleCursor => {
+                    *cursor_free = !*cursor_free;
+                    println!(
+                        "Cursor: {}",
+                        if *cursor_free {
+                            "free, click to select"
+                        } else {
+                            "captured"
+                        }
+                    );
+                }
+                Action::TogglePicking => {
+                    settings.gpu_picking = !settings.gpu_picking;
+                    println!(
+                        "Picking: {}",
+                        if settings.gpu_picking {
+                            "ID buffer"
+                        } else {
+                            "ray"
+                        }
+                    );
+                }
+                Action::DecreaseBloomThreshold => settings.bloom.adjust_threshold(-0.1),
+                Action::IncreaseBloomThreshold => settings.bloom.adjust_threshold(0.1),
+                Action::DecreaseBloomIntensity => settings.bloom.scale_intensity(0.8),
+                Action::IncreaseBloomIntensity => settings.bloom.scale_intensity(1.25),
+                Action::TogglePostPass(index) => post_processing.toggle(index),
+                // Held actions are read below
+                _ => (),
+            }
+        }
     }
 
     let now_ticks = timer.performance_counter() as f64;
@@ -356,22 +1053,70 @@ fn process_events(
     *last_ticks = now_ticks;
 
     let delta_seconds = delta_ticks / freq_ticks;
-    let camera_velocity = (delta_seconds * CAMERA_SPEED as f64) as f32;
     let seconds = timer.ticks() as f32 / 1000.0;
 
     let mouse_state = sdl2::mouse::RelativeMouseState::new(&event_pump);
-    camera.update_orientation(
-        mouse_state.x() as f32,
-        -mouse_state.y() as f32,
-        CAMERA_SENSITIVITY,
+    // The free cursor moves without turning the camera
+    let (x_offset, y_offset) = if *cursor_free {
+        (0.0, 0.0)
+    } else {
+        (mouse_state.x() as f32, -mouse_state.y() as f32)
+    };
+    // The right stick looks around as if the mouse had moved as far
+    let pad = gamepads.read();
+    let look_scale = GAMEPAD_LOOK_SPEED * delta_seconds as f32 / CAMERA_SENSITIVITY;
+    let (look_x, look_y) = (
+        pad.right_stick.0 * look_scale,
+        -pad.right_stick.1 * look_scale,
     );
 
-    for movement in current_movement {
-        match movement {
-            Some(dir) => camera.update_position(*dir, camera_velocity),
-            None => (),
+    if let Some(active) = playback {
+        match active.advance(path, delta_seconds as f32) {
+            Some((position, orientation)) => camera.set_pose(position, orientation),
+            None => {
+                *playback = None;
+                println!("Camera path finished");
+            }
+        }
+    } else if let Some(orbit) = orbit {
+        if input.is_held(Action::OrbitRotate) {
+            orbit.rotate(x_offset, y_offset, CAMERA_SENSITIVITY);
+        } else if input.is_held(Action::OrbitPan) {
+            orbit.pan(camera, x_offset, y_offset);
+        }
+        orbit.rotate(look_x, look_y, CAMERA_SENSITIVITY);
+    } else {
+        let mut movement: Vec<(Direction, f32)> = MOVEMENT
+            .iter()
+            .filter(|(action, _)| input.is_held(*action))
+            .map(|(_, direction)| (*direction, 1.0))
+            .collect();
+        // Pushing the left stick up moves forwards, the triggers move down and up
+        movement.extend([
+            (Direction::Right, pad.left_stick.0),
+            (Direction::Backward, pad.left_stick.1),
+            (Direction::Up, pad.right_trigger - pad.left_trigger),
+        ]);
+        fly.update(
+            camera,
+            &movement,
+            input.is_held(Action::Sprint),
+            (x_offset + look_x, y_offset + look_y),
+            CAMERA_SENSITIVITY,
+            delta_seconds as f32,
+        );
+        for (action, roll) in [(Action::RollLeft, -1.0), (Action::RollRight, 1.0)] {
+            if input.is_held(action) {
+                camera.roll(roll * ROLL_SPEED * delta_seconds as f32);
+            }
         }
     }
 
-    Some(seconds)
+    Some(Frame {
+        seconds,
+        delta_seconds: delta_seconds as f32,
+        resized,
+        frame_scene,
+        pick,
+    })
 }
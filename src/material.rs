@@ -0,0 +1,44 @@
+extern crate gl;
+use crate::shader::Shader;
+use crate::texture::Texture;
+use nalgebra_glm as glm;
+use std::rc::Rc;
+
+pub struct Material {
+    diffuse: Rc<Texture>,
+    specular: Rc<Texture>,
+    shininess: f32,
+    // Multiplies the diffuse texture
+    tint: glm::Vec3,
+}
+
+impl Material {
+    pub fn with_tint(mut self, tint: glm::Vec3) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn apply(&self, shader: &Shader) -> Result<(), String> {
+        shader.set_int("uMaterial.diffuse", 0)?;
+        shader.set_int("uMaterial.specular", 1)?;
+        shader.set_float("uMaterial.shininess", self.shininess)?;
+        shader.set_vec3("uMaterial.tint", &self.tint)?;
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            self.diffuse.bind();
+            gl::ActiveTexture(gl::TEXTURE1);
+            self.specular.bind();
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        Ok(())
+    }
+}
+
+pub fn create(diffuse: Rc<Texture>, specular: Rc<Texture>, shininess: f32) -> Material {
+    Material {
+        diffuse,
+        specular,
+        shininess,
+        tint: glm::vec3(1.0, 1.0, 1.0),
+    }
+}
@@ -0,0 +1,478 @@
+extern crate gl;
+use crate::bounds::{self, Bounds};
+use gl::types::*;
+use nalgebra_glm as glm;
+
+pub struct VertexAttribute {
+    pub location: GLuint,
+    pub components: GLint,
+    pub kind: GLenum,
+    pub normalised: bool,
+    pub offset: usize,
+}
+
+pub struct VertexLayout {
+    pub stride: usize,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+pub trait Vertex: Copy {
+    fn layout() -> VertexLayout;
+
+    // Used for a mesh's bounds, per-instance data has none
+    fn position(&self) -> Option<[f32; 3]> {
+        None
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct StandardVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub texture_coords: [f32; 2],
+}
+
+impl Vertex for StandardVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout {
+            stride: std::mem::size_of::<StandardVertex>(),
+            attributes: vec![
+                float_attribute(0, 3, std::mem::offset_of!(StandardVertex, position)),
+                float_attribute(1, 3, std::mem::offset_of!(StandardVertex, normal)),
+                float_attribute(2, 2, std::mem::offset_of!(StandardVertex, texture_coords)),
+            ],
+        }
+    }
+
+    fn position(&self) -> Option<[f32; 3]> {
+        Some(self.position)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TangentVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub texture_coords: [f32; 2],
+    // w is the handedness of the bitangent, cross(normal, tangent) * w
+    pub tangent: [f32; 4],
+}
+
+impl Vertex for TangentVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout {
+            stride: std::mem::size_of::<TangentVertex>(),
+            attributes: vec![
+                float_attribute(0, 3, std::mem::offset_of!(TangentVertex, position)),
+                float_attribute(1, 3, std::mem::offset_of!(TangentVertex, normal)),
+                float_attribute(2, 2, std::mem::offset_of!(TangentVertex, texture_coords)),
+                float_attribute(3, 4, std::mem::offset_of!(TangentVertex, tangent)),
+            ],
+        }
+    }
+
+    fn position(&self) -> Option<[f32; 3]> {
+        Some(self.position)
+    }
+}
+
+// Per-instance attributes, consumed at locations 4-7 (model) and 8-10 (normal matrix)
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct InstanceTransform {
+    pub model: [[f32; 4]; 4],
+    pub normal: [[f32; 3]; 3],
+}
+
+impl Vertex for InstanceTransform {
+    fn layout() -> VertexLayout {
+        let model = std::mem::offset_of!(InstanceTransform, model);
+        let normal = std::mem::offset_of!(InstanceTransform, normal);
+        let columns = (0..4)
+            .map(|column| float_attribute(4 + column, 4, model + column as usize * 16))
+            .chain(
+                (0..3).map(|column| float_attribute(8 + column, 3, normal + column as usize * 12)),
+            );
+        VertexLayout {
+            stride: std::mem::size_of::<InstanceTransform>(),
+            attributes: columns.collect(),
+        }
+    }
+}
+
+pub fn instance_transform(model: &glm::Mat4) -> InstanceTransform {
+    InstanceTransform {
+        model: (*model).into(),
+        normal: normal_matrix(model).into(),
+    }
+}
+
+// Transforms normals so they stay perpendicular under non-uniform scaling
+pub fn normal_matrix(model: &glm::Mat4) -> glm::Mat3 {
+    glm::inverse_transpose(glm::mat4_to_mat3(model))
+}
+
+impl StandardVertex {
+    fn bit_pattern(&self) -> [u32; 8] {
+        let [px, py, pz] = self.position;
+        let [nx, ny, nz] = self.normal;
+        let [u, v] = self.texture_coords;
+        [px, py, pz, nx, ny, nz, u, v].map(f32::to_bits)
+    }
+}
+
+pub fn float_attribute(location: GLuint, components: GLint, offset: usize) -> VertexAttribute {
+    VertexAttribute {
+        location,
+        components,
+        kind: gl::FLOAT,
+        normalised: false,
+        offset,
+    }
+}
+
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+pub fn compact_indices(indices: Vec<u32>, vertex_count: usize) -> Indices {
+    if vertex_count <= u16::MAX as usize + 1 {
+        Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+    } else {
+        Indices::U32(indices)
+    }
+}
+
+pub fn deduplicate(vertices: &[StandardVertex]) -> (Vec<StandardVertex>, Indices) {
+    let mut unique: Vec<StandardVertex> = Vec::new();
+    let mut lookup = std::collections::HashMap::new();
+    let indices = vertices
+        .iter()
+        .map(|vertex| {
+            *lookup.entry(vertex.bit_pattern()).or_insert_with(|| {
+                unique.push(*vertex);
+                (unique.len() - 1) as u32
+            })
+        })
+        .collect();
+    let vertex_count = unique.len();
+    (unique, compact_indices(indices, vertex_count))
+}
+
+// Area-weighted vertex normals: the unnormalised cross product of each face is
+// accumulated onto its corners, so larger faces contribute more.
+pub fn generate_normals(vertices: &mut [StandardVertex], indices: &[u32]) {
+    let mut normals = vec![glm::Vec3::zeros(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+            .map(|index| glm::Vec3::from(vertices[index as usize].position));
+        let face_normal = (b - a).cross(&(c - a));
+        for &index in triangle {
+            normals[index as usize] += face_normal;
+        }
+    }
+
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        let normal = if normal.norm_squared() > 0.0 {
+            normal.normalize()
+        } else {
+            glm::vec3(0.0, 1.0, 0.0)
+        };
+        vertex.normal = [normal.x, normal.y, normal.z];
+    }
+}
+
+// The full set of instances, followed by room for as many again as a subset
+struct InstanceBuffer {
+    id: GLuint,
+    layout: VertexLayout,
+    count: GLsizei,
+    subset_count: GLsizei,
+}
+
+impl InstanceBuffer {
+    // Without base instances (GL 4.2) a range is drawn by pointing the
+    // attributes at its first instance
+    unsafe fn point_attributes(&self, first: GLsizei) {
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+        for attribute in &self.layout.attributes {
+            gl::VertexAttribPointer(
+                attribute.location,
+                attribute.components,
+                attribute.kind,
+                attribute.normalised as GLboolean,
+                self.layout.stride as GLsizei,
+                (attribute.offset + first as usize * self.layout.stride)
+                    as *const std::os::raw::c_void,
+            );
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Instances {
+    All,
+    Subset,
+}
+
+struct IndexBuffer {
+    id: GLuint,
+    kind: GLenum,
+    count: GLsizei,
+}
+
+pub struct Mesh {
+    vao: GLuint,
+    vbo: GLuint,
+    ebo: Option<IndexBuffer>,
+    instances: Option<InstanceBuffer>,
+    vertex_count: GLsizei,
+    bounds: Option<Bounds>,
+}
+
+impl Mesh {
+    // In the mesh's own space
+    pub fn get_bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    pub fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            match &self.ebo {
+                Some(ebo) => gl::DrawElements(gl::TRIANGLES, ebo.count, ebo.kind, std::ptr::null()),
+                None => gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_count),
+            }
+        }
+    }
+
+    pub fn draw_instances(&self, which: Instances) {
+        let instances = match &self.instances {
+            Some(instances) => instances,
+            None => return,
+        };
+        let (first, count) = match which {
+            Instances::All => (0, instances.count),
+            Instances::Subset => (instances.count, instances.subset_count),
+        };
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            if first > 0 {
+                instances.point_attributes(first);
+            }
+            match &self.ebo {
+                Some(ebo) => gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    ebo.count,
+                    ebo.kind,
+                    std::ptr::null(),
+                    count,
+                ),
+                None => gl::DrawArraysInstanced(gl::TRIANGLES, 0, self.vertex_count, count),
+            }
+            if first > 0 {
+                instances.point_attributes(0);
+            }
+        }
+    }
+
+    // Replaces the full set, which is only reallocated when its size changes.
+    // Any subset is cleared.
+    pub fn set_instances<I: Vertex>(&mut self, instances: &[I]) -> Result<(), String> {
+        let size: GLsizeiptr = std::mem::size_of_val(instances)
+            .try_into()
+            .map_err(error_to_string())?;
+        let count: GLsizei = instances.len().try_into().map_err(error_to_string())?;
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            match &mut self.instances {
+                Some(buffer) if buffer.count == count => {
+                    gl::BindBuffer(gl::ARRAY_BUFFER, buffer.id);
+                }
+                _ => {
+                    let buffer = match self.instances.take() {
+                        Some(buffer) => InstanceBuffer {
+                            layout: I::layout(),
+                            count,
+                            ..buffer
+                        },
+                        None => {
+                            let mut buffer = InstanceBuffer {
+                                id: 0,
+                                layout: I::layout(),
+                                count,
+                                subset_count: 0,
+                            };
+                            gl::GenBuffers(1, &mut buffer.id);
+                            for attribute in &buffer.layout.attributes {
+                                gl::EnableVertexAttribArray(attribute.location);
+                                gl::VertexAttribDivisor(attribute.location, 1);
+                            }
+                            buffer
+                        }
+                    };
+                    buffer.point_attributes(0);
+                    gl::BufferData(
+                        gl::ARRAY_BUFFER,
+                        2 * size,
+                        std::ptr::null(),
+                        gl::DYNAMIC_DRAW,
+                    );
+                    self.instances = Some(buffer);
+                }
+            }
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                size,
+                instances.as_ptr() as *const std::os::raw::c_void,
+            );
+            gl::BindVertexArray(0);
+        }
+        if let Some(buffer) = &mut self.instances {
+            buffer.subset_count = 0;
+        }
+        Ok(())
+    }
+
+    // Written after the full set, which stays as it was, so both can be drawn
+    pub fn set_instance_subset<I: Vertex>(&mut self, instances: &[I]) -> Result<(), String> {
+        let buffer = self
+            .instances
+            .as_mut()
+            .ok_or("Mesh has no instances to take a subset of")?;
+        let count: GLsizei = instances.len().try_into().map_err(error_to_string())?;
+        if count > buffer.count {
+            return Err(format!(
+                "Instance subset of {} is larger than the {} instances",
+                count, buffer.count
+            ));
+        }
+        let offset: GLintptr = (buffer.count as usize * buffer.layout.stride)
+            .try_into()
+            .map_err(error_to_string())?;
+        let size: GLsizeiptr = std::mem::size_of_val(instances)
+            .try_into()
+            .map_err(error_to_string())?;
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer.id);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                offset,
+                size,
+                instances.as_ptr() as *const std::os::raw::c_void,
+            );
+        }
+        buffer.subset_count = count;
+        Ok(())
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(ebo) = &self.ebo {
+                gl::DeleteBuffers(1, &ebo.id);
+            }
+            if let Some(instances) = &self.instances {
+                gl::DeleteBuffers(1, &instances.id);
+            }
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+pub fn create<V: Vertex>(vertices: &[V], indices: Option<&Indices>) -> Result<Mesh, String> {
+    let layout = V::layout();
+    let stride: GLsizei = layout.stride.try_into().map_err(error_to_string())?;
+    let size: GLsizeiptr = std::mem::size_of_val(vertices)
+        .try_into()
+        .map_err(error_to_string())?;
+
+    let mut mesh = Mesh {
+        vao: 0,
+        vbo: 0,
+        ebo: None,
+        instances: None,
+        vertex_count: vertices.len().try_into().map_err(error_to_string())?,
+        bounds: bounds::from_points(
+            vertices
+                .iter()
+                .filter_map(Vertex::position)
+                .map(glm::Vec3::from),
+        ),
+    };
+    unsafe {
+        gl::GenVertexArrays(1, &mut mesh.vao);
+        gl::BindVertexArray(mesh.vao);
+
+        gl::GenBuffers(1, &mut mesh.vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            size,
+            vertices.as_ptr() as *const std::os::raw::c_void,
+            gl::STATIC_DRAW,
+        );
+
+        for attribute in &layout.attributes {
+            gl::VertexAttribPointer(
+                attribute.location,
+                attribute.components,
+                attribute.kind,
+                attribute.normalised as GLboolean,
+                stride,
+                attribute.offset as *const std::os::raw::c_void,
+            );
+            gl::EnableVertexAttribArray(attribute.location);
+        }
+    }
+
+    if let Some(indices) = indices {
+        let (data, size, kind, count) = match indices {
+            Indices::U16(values) => (
+                values.as_ptr() as *const std::os::raw::c_void,
+                std::mem::size_of_val(values.as_slice()),
+                gl::UNSIGNED_SHORT,
+                values.len(),
+            ),
+            Indices::U32(values) => (
+                values.as_ptr() as *const std::os::raw::c_void,
+                std::mem::size_of_val(values.as_slice()),
+                gl::UNSIGNED_INT,
+                values.len(),
+            ),
+        };
+
+        let size: GLsizeiptr = size.try_into().map_err(error_to_string())?;
+        let mut ebo = IndexBuffer {
+            id: 0,
+            kind,
+            count: count.try_into().map_err(error_to_string())?,
+        };
+        unsafe {
+            gl::GenBuffers(1, &mut ebo.id);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo.id);
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, size, data, gl::STATIC_DRAW);
+        }
+        mesh.ebo = Some(ebo);
+    }
+
+    unsafe {
+        gl::BindVertexArray(0);
+    }
+    Ok(mesh)
+}
+
+fn error_to_string<E>() -> fn(E) -> String
+where
+    E: std::fmt::Display,
+{
+    |e: E| e.to_string()
+}
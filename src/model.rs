@@ -0,0 +1,34 @@
+use crate::material::Material;
+use crate::mesh::Mesh;
+use nalgebra_glm as glm;
+use std::rc::Rc;
+
+pub struct Part {
+    pub name: String,
+    pub mesh: Rc<Mesh>,
+    pub material: Rc<Material>,
+}
+
+pub struct Model {
+    parts: Vec<Part>,
+    bounds_min: glm::Vec3,
+    bounds_max: glm::Vec3,
+}
+
+impl Model {
+    pub fn get_parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    pub fn get_bounds(&self) -> (glm::Vec3, glm::Vec3) {
+        (self.bounds_min, self.bounds_max)
+    }
+}
+
+pub fn create(parts: Vec<Part>, bounds_min: glm::Vec3, bounds_max: glm::Vec3) -> Model {
+    Model {
+        parts,
+        bounds_min,
+        bounds_max,
+    }
+}
@@ -0,0 +1,151 @@
+use crate::material::{self, Material};
+use crate::mesh::{self, StandardVertex};
+use crate::model::{self, Model, Part};
+use crate::texture::{self, Texture};
+use nalgebra_glm as glm;
+use std::path::Path;
+use std::rc::Rc;
+
+const DEFAULT_DIFFUSE: [f32; 3] = [0.8, 0.8, 0.8];
+const DEFAULT_SPECULAR: [f32; 3] = [0.0, 0.0, 0.0];
+const DEFAULT_SHININESS: f32 = 32.0;
+
+pub fn load(path: &Path) -> Result<Model, String> {
+    let (objects, obj_materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+        },
+    )
+    .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut materials = obj_materials
+        .map_err(|e| format!("Failed to load materials for {}: {}", path.display(), e))?
+        .iter()
+        .map(|obj_material| create_material(obj_material, directory).map(Rc::new))
+        .collect::<Result<Vec<Rc<Material>>, String>>()?;
+
+    let mut default_material = None;
+    let mut bounds_min = glm::vec3(f32::MAX, f32::MAX, f32::MAX);
+    let mut bounds_max = glm::vec3(f32::MIN, f32::MIN, f32::MIN);
+    let mut parts = Vec::with_capacity(objects.len());
+    for object in &objects {
+        let obj_mesh = &object.mesh;
+        if obj_mesh.indices.is_empty() {
+            continue;
+        }
+
+        let vertices = create_vertices(obj_mesh);
+        for vertex in &vertices {
+            let position = glm::Vec3::from(vertex.position);
+            bounds_min = glm::min2(&bounds_min, &position);
+            bounds_max = glm::max2(&bounds_max, &position);
+        }
+
+        let material = match obj_mesh.material_id {
+            Some(id) if id < materials.len() => id,
+            _ => *default_material.get_or_insert(materials.len()),
+        };
+        if material == materials.len() {
+            materials.push(Rc::new(material::create(
+                Rc::new(texture::from_colour(DEFAULT_DIFFUSE)?),
+                Rc::new(texture::from_colour(DEFAULT_SPECULAR)?),
+                DEFAULT_SHININESS,
+            )));
+        }
+
+        let indices = mesh::compact_indices(obj_mesh.indices.clone(), vertices.len());
+        parts.push(Part {
+            name: object.name.clone(),
+            mesh: Rc::new(mesh::create(&vertices, Some(&indices))?),
+            material: materials[material].clone(),
+        });
+    }
+
+    if parts.is_empty() {
+        return Err(format!("{} contains no triangles", path.display()));
+    }
+    Ok(model::create(parts, bounds_min, bounds_max))
+}
+
+fn create_vertices(obj_mesh: &tobj::Mesh) -> Vec<StandardVertex> {
+    let vertex_count = obj_mesh.positions.len() / 3;
+    let has_normals = obj_mesh.normals.len() == obj_mesh.positions.len();
+    let has_texture_coords = obj_mesh.texcoords.len() == vertex_count * 2;
+
+    let mut vertices: Vec<StandardVertex> = (0..vertex_count)
+        .map(|i| StandardVertex {
+            position: [
+                obj_mesh.positions[i * 3],
+                obj_mesh.positions[i * 3 + 1],
+                obj_mesh.positions[i * 3 + 2],
+            ],
+            normal: if has_normals {
+                [
+                    obj_mesh.normals[i * 3],
+                    obj_mesh.normals[i * 3 + 1],
+                    obj_mesh.normals[i * 3 + 2],
+                ]
+            } else {
+                [0.0, 0.0, 0.0]
+            },
+            // OBJ puts v = 0 at the bottom of the image, textures are uploaded top row first
+            texture_coords: if has_texture_coords {
+                [
+                    obj_mesh.texcoords[i * 2],
+                    1.0 - obj_mesh.texcoords[i * 2 + 1],
+                ]
+            } else {
+                [0.0, 0.0]
+            },
+        })
+        .collect();
+
+    if !has_normals {
+        mesh::generate_normals(&mut vertices, &obj_mesh.indices);
+    }
+    vertices
+}
+
+fn create_material(obj_material: &tobj::Material, directory: &Path) -> Result<Material, String> {
+    let diffuse = load_texture_or_colour(
+        obj_material.diffuse_texture.as_deref(),
+        obj_material.diffuse.unwrap_or(DEFAULT_DIFFUSE),
+        directory,
+    )?;
+    let specular = load_texture_or_colour(
+        obj_material.specular_texture.as_deref(),
+        obj_material.specular.unwrap_or(DEFAULT_SPECULAR),
+        directory,
+    )?;
+    let shininess = obj_material
+        .shininess
+        .filter(|shininess| *shininess > 0.0)
+        .unwrap_or(DEFAULT_SHININESS);
+
+    Ok(material::create(
+        Rc::new(diffuse),
+        Rc::new(specular),
+        shininess,
+    ))
+}
+
+fn load_texture_or_colour(
+    file_name: Option<&str>,
+    colour: [f32; 3],
+    directory: &Path,
+) -> Result<Texture, String> {
+    match file_name {
+        Some(file_name) => {
+            let path = directory.join(file_name);
+            let data = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            texture::create(&data)
+        }
+        None => texture::from_colour(colour),
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+pub struct Options {
+    pub post_passes: Vec<PathBuf>,
+    pub model: Option<PathBuf>,
+    pub gltf: Option<PathBuf>,
+    pub scene: Option<PathBuf>,
+    // Overrides the scene's cube count
+    pub cube_count: Option<usize>,
+    // Overrides the scene's seed
+    pub seed: Option<u64>,
+    // Played back on a loop from the start, and where recorded paths are saved
+    pub camera_path: Option<PathBuf>,
+    // Rebinds the actions it lists, see default_bindings.ron
+    pub bindings: Option<PathBuf>,
+}
+
+pub fn parse() -> Result<Options, String> {
+    let mut options = Options {
+        post_passes: Vec::new(),
+        model: None,
+        gltf: None,
+        scene: None,
+        cube_count: None,
+        seed: None,
+        camera_path: None,
+        bindings: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--post" => options
+                .post_passes
+                .push(PathBuf::from(expect_value(&arg, args.next())?)),
+            "--model" => options.model = Some(PathBuf::from(expect_value(&arg, args.next())?)),
+            "--gltf" => options.gltf = Some(PathBuf::from(expect_value(&arg, args.next())?)),
+            "--scene" => options.scene = Some(PathBuf::from(expect_value(&arg, args.next())?)),
+            "--cubes" => {
+                options.cube_count = Some(
+                    expect_value(&arg, args.next())?
+                        .parse()
+                        .map_err(|e| format!("Invalid value for {}: {}", arg, e))?,
+                )
+            }
+            "--camera-path" => {
+                options.camera_path = Some(PathBuf::from(expect_value(&arg, args.next())?))
+            }
+            "--bindings" => {
+                options.bindings = Some(PathBuf::from(expect_value(&arg, args.next())?))
+            }
+            "--seed" => {
+                options.seed = Some(
+                    expect_value(&arg, args.next())?
+                        .parse()
+                        .map_err(|e| format!("Invalid value for {}: {}", arg, e))?,
+                )
+            }
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn expect_value(arg: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or(format!("Missing value for {}", arg))
+}
@@ -0,0 +1,75 @@
+use crate::bounds::Bounds;
+use crate::camera::{self, Camera, Projection};
+use nalgebra_glm as glm;
+
+const ZOOM_STEP: f32 = 1.1;
+const MIN_DISTANCE: f32 = 0.1;
+const MAX_DISTANCE: f32 = 500.0;
+// Fraction of the distance to the target moved per pixel of mouse travel
+const PAN_SPEED: f32 = 0.002;
+
+// Keeps the camera looking at `target` from `distance` away; yaw and pitch are
+// in degrees, as for the fly camera
+pub struct Orbit {
+    target: glm::Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Orbit {
+    pub fn rotate(&mut self, x_offset: f32, y_offset: f32, sensitivity: f32) {
+        self.yaw += sensitivity * x_offset;
+        self.pitch = num::clamp(self.pitch + sensitivity * y_offset, -89.0, 89.0);
+    }
+
+    // Positive steps move closer, one per notch of the mouse wheel
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance = num::clamp(
+            self.distance * ZOOM_STEP.powf(-steps),
+            MIN_DISTANCE,
+            MAX_DISTANCE,
+        );
+    }
+
+    // Slides the target across the view so the scene follows the mouse
+    pub fn pan(&mut self, camera: &Camera, x_offset: f32, y_offset: f32) {
+        let scale = self.distance * PAN_SPEED;
+        self.target -= (camera.get_right() * x_offset + camera.get_up() * y_offset) * scale;
+    }
+
+    // Moves back far enough that the box's bounding sphere fits the view, or
+    // for orthographic views sizes the view to fit it
+    pub fn frame(&mut self, bounds: &Bounds, camera: &mut Camera) {
+        let radius = (glm::length(&bounds.size()) / 2.0).max(MIN_DISTANCE);
+        self.target = bounds.centre();
+        self.distance = match camera.get_projection() {
+            Projection::Perspective { y_fov, .. } | Projection::ReversedInfinite { y_fov, .. } => {
+                let x_fov = 2.0 * ((y_fov / 2.0).tan() * camera.get_aspect()).atan();
+                radius / (y_fov.min(x_fov) / 2.0).sin()
+            }
+            // The size on screen doesn't change with distance, only clipping does
+            Projection::Orthographic { .. } => {
+                camera.set_orthographic_height(2.0 * radius * (1.0 / camera.get_aspect()).max(1.0));
+                2.0 * radius
+            }
+        }
+        .min(MAX_DISTANCE);
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        let front = camera::front_from_angles(self.yaw, self.pitch);
+        camera.place(self.target - front * self.distance, self.yaw, self.pitch);
+    }
+}
+
+// Orbits the point `distance` in front of the camera, so switching doesn't move the view
+pub fn around(camera: &Camera, distance: f32) -> Orbit {
+    let (yaw, pitch) = camera.get_angles();
+    Orbit {
+        target: camera.get_position() + camera.get_front() * distance,
+        distance,
+        yaw,
+        pitch,
+    }
+}
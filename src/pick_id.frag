@@ -0,0 +1,10 @@
+#version 330 core
+
+uniform int uId;
+
+out uint aId;
+
+void main()
+{
+    aId = uint(uId);
+}
@@ -0,0 +1,146 @@
+use crate::bounds::Bounds;
+use crate::camera::Camera;
+use crate::framebuffer::{self, ColourFormat, Framebuffer};
+use crate::mesh::Mesh;
+use crate::scene::{NodeId, Scene};
+use crate::shader::{self, Shader};
+use nalgebra_glm as glm;
+
+pub struct Ray {
+    pub origin: glm::Vec3,
+    pub direction: glm::Vec3,
+}
+
+impl Ray {
+    // Distance along the direction where the ray enters the box, or 0 when it
+    // starts inside; None when it misses or the box is behind it
+    pub fn intersect(&self, bounds: &Bounds) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (bounds.min[axis] - self.origin[axis]) * inverse;
+            let t1 = (bounds.max[axis] - self.origin[axis]) * inverse;
+            // A NaN from a ray lying in a slab's plane is ignored by min and max
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    // Distances along the transformed ray match the original's
+    pub fn transform(&self, matrix: &glm::Mat4) -> Ray {
+        let origin = matrix * glm::vec4(self.origin.x, self.origin.y, self.origin.z, 1.0);
+        let direction =
+            matrix * glm::vec4(self.direction.x, self.direction.y, self.direction.z, 0.0);
+        Ray {
+            origin: glm::vec4_to_vec3(&origin),
+            direction: direction.xyz(),
+        }
+    }
+}
+
+// Something that can be picked: a node and the mesh drawn with its transform
+pub struct Target<'a> {
+    pub node: NodeId,
+    pub mesh: &'a Mesh,
+}
+
+// From the near plane through the pixel at (x, y), counted from the window's
+// top left, with a unit direction
+pub fn cursor_ray(camera: &Camera, x: i32, y: i32, width: u32, height: u32) -> Ray {
+    let ndc_x = 2.0 * (x as f32 + 0.5) / width as f32 - 1.0;
+    let ndc_y = 1.0 - 2.0 * (y as f32 + 0.5) / height as f32;
+    // Reversed-Z puts the near plane at 1 and infinity at 0
+    let (near_depth, far_depth) = if camera.get_projection().is_reversed_z() {
+        (1.0, 0.5)
+    } else {
+        (-1.0, 1.0)
+    };
+    let inverse = glm::inverse(&camera.get_view_projection());
+    let unproject = |depth: f32| {
+        let point = inverse * glm::vec4(ndc_x, ndc_y, depth, 1.0);
+        point.xyz() / point.w
+    };
+    let origin = unproject(near_depth);
+    Ray {
+        origin,
+        direction: glm::normalize(&(unproject(far_depth) - origin)),
+    }
+}
+
+// The nearest target the ray hits, tested against its mesh's bounds in the
+// mesh's own space so rotated boxes fit tightly
+pub fn pick(ray: &Ray, targets: &[Target], scene: &Scene) -> Option<NodeId> {
+    targets
+        .iter()
+        .filter_map(|target| {
+            let bounds = target.mesh.get_bounds()?;
+            let world = scene.get_world_transform(target.node);
+            let distance = ray.transform(&glm::inverse(world)).intersect(&bounds)?;
+            Some((target.node, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(node, _)| node)
+}
+
+// Renders each target's index + 1 into an integer framebuffer and reads back
+// the one under the cursor, so the hit follows the actual triangles
+pub struct IdBuffer {
+    framebuffer: Framebuffer,
+    shader: Shader,
+}
+
+impl IdBuffer {
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.framebuffer.resize(width, height)
+    }
+
+    // Expects the depth test and clear value set up for the camera's projection
+    pub fn pick(
+        &self,
+        camera: &Camera,
+        targets: &[Target],
+        scene: &Scene,
+        x: i32,
+        y: i32,
+    ) -> Result<Option<NodeId>, String> {
+        self.framebuffer.bind()?;
+        unsafe {
+            gl::ClearBufferuiv(gl::COLOR, 0, [0_u32; 4].as_ptr());
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        self.shader.enable();
+        self.shader
+            .set_mat4("uViewProjection", &camera.get_view_projection())?;
+        for (i, target) in targets.iter().enumerate() {
+            self.shader
+                .set_mat4("uModel", scene.get_world_transform(target.node))?;
+            self.shader.set_int("uId", i as i32 + 1)?;
+            target.mesh.draw();
+        }
+
+        let (_, height) = self.framebuffer.get_size();
+        if x < 0 || y < 0 || y as u32 >= height {
+            return Ok(None);
+        }
+        let id = self.framebuffer.read_uint(x as u32, height - 1 - y as u32) as usize;
+        Ok(id
+            .checked_sub(1)
+            .and_then(|index| targets.get(index))
+            .map(|target| target.node))
+    }
+}
+
+pub fn create_id_buffer(width: u32, height: u32) -> Result<IdBuffer, String> {
+    Ok(IdBuffer {
+        framebuffer: framebuffer::create(width, height, ColourFormat::R32Ui, true)?,
+        shader: shader::compile_from_sources(
+            include_str!("light_cube.vert"),
+            include_str!("pick_id.frag"),
+        )?,
+    })
+}
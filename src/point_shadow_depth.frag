@@ -0,0 +1,11 @@
+#version 330 core
+
+uniform vec3 uLightPos;
+uniform float uFarPlane;
+
+in vec4 aFragPos;
+
+void main()
+{
+    gl_FragDepth = length(aFragPos.xyz - uLightPos) / uFarPlane;
+}
@@ -0,0 +1,12 @@
+#version 330 core
+layout (location = 0) in vec3 inVertices;
+layout (location = 4) in mat4 inInstanceModel;
+
+uniform bool uInstanced;
+uniform mat4 uModel;
+
+void main()
+{
+    mat4 model = uInstanced ? inInstanceModel : uModel;
+    gl_Position = model * vec4(inVertices, 1.0);
+}
@@ -0,0 +1,235 @@
+extern crate gl;
+use crate::framebuffer::{self, ColourFormat, Framebuffer};
+use crate::shader::{self, Shader};
+use gl::types::*;
+
+pub enum Input {
+    Scene,
+    Previous,
+}
+
+pub enum Uniform {
+    Int(i32),
+    Float(f32),
+}
+
+pub struct Pass {
+    name: String,
+    shader: Shader,
+    inputs: Vec<(String, Input)>,
+    uniforms: Vec<(String, Uniform)>,
+    enabled: bool,
+}
+
+impl Pass {
+    pub fn with_input(mut self, sampler: &str, input: Input) -> Self {
+        self.inputs.push((sampler.to_string(), input));
+        self
+    }
+
+    pub fn with_uniform(mut self, name: &str, value: Uniform) -> Self {
+        self.set_uniform(name, value);
+        self
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn set_uniform(&mut self, name: &str, value: Uniform) {
+        match self
+            .uniforms
+            .iter_mut()
+            .find(|(existing, _)| existing == name)
+        {
+            Some((_, existing)) => *existing = value,
+            None => self.uniforms.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply_uniforms(&self) -> Result<(), String> {
+        for (name, value) in &self.uniforms {
+            match value {
+                Uniform::Int(value) => self.shader.set_int(name, *value)?,
+                Uniform::Float(value) => self.shader.set_float(name, *value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn pass(name: &str, fragment_source: &str) -> Result<Pass, String> {
+    let shader = shader::compile_from_sources(include_str!("fullscreen.vert"), fragment_source)
+        .map_err(|e| format!("Post-process pass '{}': {}", name, e))?;
+    Ok(Pass {
+        name: name.to_string(),
+        shader,
+        inputs: Vec::new(),
+        uniforms: Vec::new(),
+        enabled: true,
+    })
+}
+
+pub fn pass_from_file(path: &std::path::Path) -> Result<Pass, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+    Ok(pass(&name, &source)?.with_input("uImage", Input::Previous))
+}
+
+pub struct Pipeline {
+    passes: Vec<Pass>,
+    ping_pong: [Framebuffer; 2],
+    vao: GLuint,
+}
+
+impl Pipeline {
+    pub fn add(&mut self, pass: Pass) {
+        self.passes.push(pass);
+    }
+
+    pub fn get_pass_mut(&mut self, name: &str) -> Option<&mut Pass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(pass) = self.passes.get_mut(index) {
+            pass.enabled = !pass.enabled;
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        for target in &mut self.ping_pong {
+            target.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    pub fn run(
+        &self,
+        scene: &Framebuffer,
+        screen_width: u32,
+        screen_height: u32,
+    ) -> Result<(), String> {
+        let last_enabled = self.passes.iter().rposition(|pass| pass.enabled);
+
+        let mut previous = scene;
+        let mut ping = 0;
+        // The caller's vertex array is bound again afterwards
+        let mut caller_vao: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut caller_vao);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            if !pass.enabled {
+                continue;
+            }
+
+            let target = if Some(index) == last_enabled {
+                framebuffer::bind_default(screen_width, screen_height)?;
+                None
+            } else {
+                let target = &self.ping_pong[ping];
+                ping = 1 - ping;
+                target.bind()?;
+                Some(target)
+            };
+
+            pass.shader.enable();
+            for (unit, (sampler, input)) in pass.inputs.iter().enumerate() {
+                let source = match input {
+                    Input::Scene => scene,
+                    Input::Previous => previous,
+                };
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit as GLuint);
+                }
+                source.bind_colour_texture();
+                pass.shader.set_int(sampler, unit as i32)?;
+            }
+            pass.apply_uniforms()?;
+
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+
+            if let Some(target) = target {
+                previous = target;
+            }
+        }
+
+        if last_enabled.is_none() {
+            blit_to_screen(scene, screen_width, screen_height)?;
+        }
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindVertexArray(caller_vao as GLuint);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+pub fn create(width: u32, height: u32) -> Result<Pipeline, String> {
+    let mut vao: GLuint = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+    Ok(Pipeline {
+        passes: Vec::new(),
+        ping_pong: [
+            framebuffer::create(width, height, ColourFormat::Rgba16F, false)?,
+            framebuffer::create(width, height, ColourFormat::Rgba16F, false)?,
+        ],
+        vao,
+    })
+}
+
+fn blit_to_screen(scene: &Framebuffer, width: u32, height: u32) -> Result<(), String> {
+    let (scene_width, scene_height) = scene.get_size();
+    framebuffer::bind_default(width, height)?;
+    scene.bind_read();
+    unsafe {
+        gl::BlitFramebuffer(
+            0,
+            0,
+            scene_width as GLint,
+            scene_height as GLint,
+            0,
+            0,
+            width as GLint,
+            height as GLint,
+            gl::COLOR_BUFFER_BIT,
+            gl::LINEAR,
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,457 @@
+use crate::mesh::{self, Mesh, TangentVertex};
+use nalgebra_glm as glm;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+pub struct Geometry {
+    pub vertices: Vec<TangentVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn upload(&self) -> Result<Mesh, String> {
+        let indices = mesh::compact_indices(self.indices.clone(), self.vertices.len());
+        mesh::create(&self.vertices, Some(&indices))
+    }
+
+    fn append(&mut self, other: Geometry) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| index + offset));
+    }
+}
+
+// A point on the outline revolved by `revolve`, in (radius, height) space.
+// Outlines run from top to bottom on the outward facing side.
+struct ProfilePoint {
+    radius: f32,
+    height: f32,
+    normal: (f32, f32),
+    v: f32,
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Geometry {
+    let profile = (0..=rings)
+        .map(|ring| {
+            let theta = PI * ring as f32 / rings as f32;
+            ProfilePoint {
+                radius: radius * theta.sin(),
+                height: radius * theta.cos(),
+                normal: (theta.sin(), theta.cos()),
+                v: ring as f32 / rings as f32,
+            }
+        })
+        .collect::<Vec<_>>();
+    with_tangents(revolve(&profile, segments))
+}
+
+pub fn icosphere(radius: f32, subdivisions: u32) -> Geometry {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions: Vec<glm::Vec3> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| glm::normalize(&glm::vec3(x, y, z)))
+    .collect();
+    #[rustfmt::skip]
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let position = (positions[a as usize] + positions[b as usize]) * 0.5;
+                positions.push(glm::normalize(&position));
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut vertices: Vec<TangentVertex> = positions
+        .iter()
+        .map(|normal| {
+            let u = 0.5 - normal.z.atan2(normal.x) / (2.0 * PI);
+            let v = normal.y.clamp(-1.0, 1.0).acos() / PI;
+            vertex(normal * radius, *normal, [u, v])
+        })
+        .collect();
+
+    // Triangles straddling the u = 0/1 seam would interpolate across the whole
+    // texture, so their low side is duplicated with u shifted past 1
+    let mut wrapped = HashMap::new();
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+    for triangle in &triangles {
+        let us = triangle.map(|index| vertices[index as usize].texture_coords[0]);
+        let max_u = us[0].max(us[1]).max(us[2]);
+        for (&index, &u) in triangle.iter().zip(&us) {
+            if max_u - u > 0.5 {
+                indices.push(*wrapped.entry(index).or_insert_with(|| {
+                    let mut duplicate = vertices[index as usize];
+                    duplicate.texture_coords[0] += 1.0;
+                    vertices.push(duplicate);
+                    vertices.len() as u32 - 1
+                }));
+            } else {
+                indices.push(index);
+            }
+        }
+    }
+
+    with_tangents(Geometry { vertices, indices })
+}
+
+pub fn plane(size: f32, subdivisions: u32) -> Geometry {
+    let cells = subdivisions.max(1);
+    let mut vertices = Vec::with_capacity(((cells + 1) * (cells + 1)) as usize);
+    for row in 0..=cells {
+        for column in 0..=cells {
+            let (u, v) = (column as f32 / cells as f32, row as f32 / cells as f32);
+            vertices.push(vertex(
+                glm::vec3((u - 0.5) * size, 0.0, (v - 0.5) * size),
+                glm::vec3(0.0, 1.0, 0.0),
+                [u, v],
+            ));
+        }
+    }
+    let indices = grid_indices(cells, cells, |_| false);
+    with_tangents(Geometry { vertices, indices })
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Geometry {
+    let half_height = height / 2.0;
+    let mut geometry = revolve(
+        &[
+            ProfilePoint {
+                radius,
+                height: half_height,
+                normal: (1.0, 0.0),
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                height: -half_height,
+                normal: (1.0, 0.0),
+                v: 1.0,
+            },
+        ],
+        segments,
+    );
+    geometry.append(disc(radius, half_height, true, segments));
+    geometry.append(disc(radius, -half_height, false, segments));
+    with_tangents(geometry)
+}
+
+pub fn cone(radius: f32, height: f32, segments: u32) -> Geometry {
+    let half_height = height / 2.0;
+    let slope = (height, radius);
+    let length = (slope.0 * slope.0 + slope.1 * slope.1).sqrt();
+    let normal = (slope.0 / length, slope.1 / length);
+    let mut geometry = revolve(
+        &[
+            ProfilePoint {
+                radius: 0.0,
+                height: half_height,
+                normal,
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                height: -half_height,
+                normal,
+                v: 1.0,
+            },
+        ],
+        segments,
+    );
+    geometry.append(disc(radius, -half_height, false, segments));
+    with_tangents(geometry)
+}
+
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Geometry {
+    let profile = (0..=sides)
+        .map(|side| {
+            let theta = 2.0 * PI * side as f32 / sides as f32;
+            ProfilePoint {
+                radius: major_radius + minor_radius * theta.sin(),
+                height: minor_radius * theta.cos(),
+                normal: (theta.sin(), theta.cos()),
+                v: side as f32 / sides as f32,
+            }
+        })
+        .collect::<Vec<_>>();
+    with_tangents(revolve(&profile, segments))
+}
+
+// `height` is the length of the cylindrical section between the two hemispheres
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Geometry {
+    let half_height = height / 2.0;
+    let length = PI * radius + height;
+    let hemisphere = |offset: f32, first_theta: f32, arc_offset: f32| {
+        (0..=rings).map(move |ring| {
+            let arc = PI / 2.0 * ring as f32 / rings as f32;
+            let theta = first_theta + arc;
+            ProfilePoint {
+                radius: radius * theta.sin(),
+                height: offset + radius * theta.cos(),
+                normal: (theta.sin(), theta.cos()),
+                v: (arc_offset + arc * radius) / length,
+            }
+        })
+    };
+    let profile = hemisphere(half_height, 0.0, 0.0)
+        .chain(hemisphere(
+            -half_height,
+            PI / 2.0,
+            PI / 2.0 * radius + height,
+        ))
+        .collect::<Vec<_>>();
+    with_tangents(revolve(&profile, segments))
+}
+
+fn revolve(profile: &[ProfilePoint], segments: u32) -> Geometry {
+    let mut vertices = Vec::with_capacity(profile.len() * (segments as usize + 1));
+    for point in profile {
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let (sin, cos) = (2.0 * PI * u).sin_cos();
+            vertices.push(vertex(
+                glm::vec3(point.radius * cos, point.height, -point.radius * sin),
+                glm::vec3(point.normal.0 * cos, point.normal.1, -point.normal.0 * sin),
+                [u, point.v],
+            ));
+        }
+    }
+
+    // Rows collapsed onto the axis only need one triangle per quad
+    let indices = grid_indices(profile.len() as u32 - 1, segments, |row| {
+        profile[row as usize].radius.abs() < 1e-6
+    });
+    Geometry { vertices, indices }
+}
+
+fn disc(radius: f32, height: f32, facing_up: bool, segments: u32) -> Geometry {
+    let normal = glm::vec3(0.0, if facing_up { 1.0 } else { -1.0 }, 0.0);
+    let mut vertices = vec![vertex(glm::vec3(0.0, height, 0.0), normal, [0.5, 0.5])];
+    for segment in 0..=segments {
+        let (sin, cos) = (2.0 * PI * segment as f32 / segments as f32).sin_cos();
+        let v = if facing_up {
+            0.5 - 0.5 * sin
+        } else {
+            0.5 + 0.5 * sin
+        };
+        vertices.push(vertex(
+            glm::vec3(radius * cos, height, -radius * sin),
+            normal,
+            [0.5 + 0.5 * cos, v],
+        ));
+    }
+
+    let indices = (1..=segments)
+        .flat_map(|i| {
+            if facing_up {
+                [0, i, i + 1]
+            } else {
+                [0, i + 1, i]
+            }
+        })
+        .collect();
+    Geometry { vertices, indices }
+}
+
+// Quads between consecutive rows of `columns + 1` vertices, wound counter-clockwise
+// when the rows advance towards the viewer's bottom and columns to their right
+fn grid_indices(rows: u32, columns: u32, is_collapsed: impl Fn(u32) -> bool) -> Vec<u32> {
+    let stride = columns + 1;
+    let mut indices = Vec::with_capacity((rows * columns * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * stride + column;
+            let b = a + stride;
+            let (c, d) = (b + 1, a + 1);
+            if !is_collapsed(row + 1) {
+                indices.extend([a, b, c]);
+            }
+            if !is_collapsed(row) {
+                indices.extend([a, c, d]);
+            }
+        }
+    }
+    indices
+}
+
+fn vertex(position: glm::Vec3, normal: glm::Vec3, texture_coords: [f32; 2]) -> TangentVertex {
+    TangentVertex {
+        position: position.into(),
+        normal: normal.into(),
+        texture_coords,
+        tangent: [0.0, 0.0, 0.0, 1.0],
+    }
+}
+
+// Per-triangle tangents from the UV gradients, accumulated per vertex and then
+// made orthogonal to the vertex normal
+fn with_tangents(mut geometry: Geometry) -> Geometry {
+    let vertex_count = geometry.vertices.len();
+    let mut tangents = vec![glm::Vec3::zeros(); vertex_count];
+    let mut bitangents = vec![glm::Vec3::zeros(); vertex_count];
+    for triangle in geometry.indices.chunks_exact(3) {
+        let [a, b, c] =
+            [triangle[0], triangle[1], triangle[2]].map(|index| geometry.vertices[index as usize]);
+        let edge_1 = glm::Vec3::from(b.position) - glm::Vec3::from(a.position);
+        let edge_2 = glm::Vec3::from(c.position) - glm::Vec3::from(a.position);
+        let (du_1, dv_1) = (
+            b.texture_coords[0] - a.texture_coords[0],
+            b.texture_coords[1] - a.texture_coords[1],
+        );
+        let (du_2, dv_2) = (
+            c.texture_coords[0] - a.texture_coords[0],
+            c.texture_coords[1] - a.texture_coords[1],
+        );
+        let determinant = du_1 * dv_2 - du_2 * dv_1;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let tangent = (edge_1 * dv_2 - edge_2 * dv_1) / determinant;
+        let bitangent = (edge_2 * du_1 - edge_1 * du_2) / determinant;
+        for &index in triangle {
+            tangents[index as usize] += tangent;
+            bitangents[index as usize] += bitangent;
+        }
+    }
+
+    for ((vertex, tangent), bitangent) in geometry.vertices.iter_mut().zip(tangents).zip(bitangents)
+    {
+        let normal = glm::Vec3::from(vertex.normal);
+        let mut tangent = tangent - normal * normal.dot(&tangent);
+        if tangent.norm_squared() < 1e-12 {
+            let axis = if normal.x.abs() < 0.9 {
+                glm::vec3(1.0, 0.0, 0.0)
+            } else {
+                glm::vec3(0.0, 0.0, 1.0)
+            };
+            tangent = axis - normal * normal.dot(&axis);
+        }
+        let tangent = tangent.normalize();
+        let handedness = if normal.cross(&tangent).dot(&bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    }
+    geometry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> Vec<(&'static str, Geometry)> {
+        vec![
+            ("uv_sphere", uv_sphere(1.5, 24, 12)),
+            ("icosphere", icosphere(1.5, 3)),
+            ("plane", plane(4.0, 8)),
+            ("cylinder", cylinder(0.5, 2.0, 24)),
+            ("cone", cone(0.5, 2.0, 24)),
+            ("torus", torus(1.0, 0.25, 32, 16)),
+            ("capsule", capsule(0.5, 1.0, 24, 8)),
+        ]
+    }
+
+    #[test]
+    fn normals_are_unit_length() {
+        for (name, geometry) in all() {
+            for vertex in &geometry.vertices {
+                let length = glm::Vec3::from(vertex.normal).norm();
+                assert!(
+                    (length - 1.0).abs() < 1e-4,
+                    "{}: normal length {}",
+                    name,
+                    length
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn winding_is_counter_clockwise_from_the_normal_side() {
+        for (name, geometry) in all() {
+            assert!(!geometry.indices.is_empty(), "{}: no triangles", name);
+            for triangle in geometry.indices.chunks_exact(3) {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+                    .map(|index| geometry.vertices[index as usize]);
+                let face_normal = (glm::Vec3::from(b.position) - glm::Vec3::from(a.position))
+                    .cross(&(glm::Vec3::from(c.position) - glm::Vec3::from(a.position)));
+                assert!(face_normal.norm() > 1e-8, "{}: degenerate triangle", name);
+
+                let vertex_normal = glm::Vec3::from(a.normal)
+                    + glm::Vec3::from(b.normal)
+                    + glm::Vec3::from(c.normal);
+                assert!(
+                    face_normal.dot(&vertex_normal) > 0.0,
+                    "{}: triangle {:?} is wound against its normals",
+                    name,
+                    triangle
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_are_unit_length_and_orthogonal_to_normals() {
+        for (name, geometry) in all() {
+            for vertex in &geometry.vertices {
+                let tangent = glm::vec3(vertex.tangent[0], vertex.tangent[1], vertex.tangent[2]);
+                let normal = glm::Vec3::from(vertex.normal);
+                assert!(
+                    (tangent.norm() - 1.0).abs() < 1e-4,
+                    "{}: tangent length",
+                    name
+                );
+                assert!(
+                    tangent.dot(&normal).abs() < 1e-4,
+                    "{}: tangent not orthogonal",
+                    name
+                );
+                assert_eq!(vertex.tangent[3].abs(), 1.0, "{}: handedness", name);
+            }
+        }
+    }
+
+    #[test]
+    fn indices_are_in_range() {
+        for (name, geometry) in all() {
+            let count = geometry.vertices.len() as u32;
+            assert!(
+                geometry.indices.iter().all(|&index| index < count),
+                "{}: index out of range",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::bounds::Bounds;
+use crate::frustum::{Counts, Frustum};
+use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::material::Material;
+use crate::mesh::{self, Mesh};
+use crate::shader::Shader;
+use nalgebra_glm as glm;
+use std::rc::Rc;
+
+pub type NodeId = usize;
+
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+}
+
+impl Transform {
+    pub fn matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+}
+
+pub fn identity() -> Transform {
+    translation(glm::Vec3::zeros())
+}
+
+pub fn translation(translation: glm::Vec3) -> Transform {
+    Transform {
+        translation,
+        rotation: glm::quat_identity(),
+        scale: glm::vec3(1.0, 1.0, 1.0),
+    }
+}
+
+// Light positions and directions are given in the space of the node they are
+// attached to; cameras look down their node's -Z axis.
+pub enum Component {
+    Renderable {
+        mesh: Rc<Mesh>,
+        material: Rc<Material>,
+    },
+    DirectionalLight(DirectionalLight),
+    PointLight(PointLight),
+    SpotLight(SpotLight),
+    Camera {
+        // None for orthographic cameras
+        y_fov: Option<f32>,
+    },
+}
+
+pub struct SceneCamera {
+    pub position: glm::Vec3,
+    pub front: glm::Vec3,
+    pub y_fov: Option<f32>,
+}
+
+struct Node {
+    name: String,
+    children: Vec<NodeId>,
+    local: Transform,
+    world: glm::Mat4,
+    dirty: bool,
+    // Whether the last update recomputed `world`
+    moved: bool,
+    components: Vec<Component>,
+}
+
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, local: Transform) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            children: Vec::new(),
+            local,
+            world: glm::one(),
+            dirty: true,
+            moved: false,
+            components: Vec::new(),
+        });
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    pub fn add_component(&mut self, id: NodeId, component: Component) {
+        self.nodes[id].components.push(component);
+    }
+
+    pub fn set_rotation(&mut self, id: NodeId, rotation: glm::Quat) {
+        let node = &mut self.nodes[id];
+        node.local.rotation = rotation;
+        node.dirty = true;
+    }
+
+    pub fn get_name(&self, id: NodeId) -> &str {
+        &self.nodes[id].name
+    }
+
+    pub fn get_local_transform(&self, id: NodeId) -> &Transform {
+        &self.nodes[id].local
+    }
+
+    // Only valid after `update` has run since the last change
+    pub fn get_world_transform(&self, id: NodeId) -> &glm::Mat4 {
+        &self.nodes[id].world
+    }
+
+    pub fn was_moved(&self, id: NodeId) -> bool {
+        self.nodes[id].moved
+    }
+
+    // Recomputes the world matrices of dirty nodes and everything below them
+    pub fn update(&mut self) {
+        for i in 0..self.roots.len() {
+            self.update_node(self.roots[i], &glm::one(), false);
+        }
+    }
+
+    fn update_node(&mut self, id: NodeId, parent_world: &glm::Mat4, parent_changed: bool) {
+        let node = &mut self.nodes[id];
+        let changed = parent_changed || node.dirty;
+        node.moved = changed;
+        if changed {
+            node.world = parent_world * node.local.matrix();
+            node.dirty = false;
+        }
+
+        let world = node.world;
+        for i in 0..self.nodes[id].children.len() {
+            self.update_node(self.nodes[id].children[i], &world, changed);
+        }
+    }
+
+    // Renderables whose bounds fall outside `frustum` are skipped, ones without
+    // bounds are always drawn
+    pub fn draw(
+        &self,
+        shader: &Shader,
+        with_materials: bool,
+        frustum: Option<&Frustum>,
+    ) -> Result<Counts, String> {
+        let mut counts = Counts::default();
+        for node in &self.nodes {
+            for component in &node.components {
+                if let Component::Renderable { mesh, material } = component {
+                    let outside = match (frustum, mesh.get_bounds()) {
+                        (Some(frustum), Some(bounds)) => {
+                            !frustum.intersects(&bounds.transform(&node.world))
+                        }
+                        _ => false,
+                    };
+                    if outside {
+                        counts.culled += 1;
+                        continue;
+                    }
+                    counts.drawn += 1;
+                    shader.set_mat4("uModel", &node.world)?;
+                    if with_materials {
+                        shader.set_mat3("uNormalMatrix", &mesh::normal_matrix(&node.world))?;
+                        material.apply(shader)?;
+                    }
+                    mesh.draw();
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    // Every node with a mesh, once per mesh
+    pub fn renderables(&self) -> Vec<(NodeId, &Mesh)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(id, node)| {
+                node.components
+                    .iter()
+                    .filter_map(move |component| match component {
+                        Component::Renderable { mesh, .. } => Some((id, mesh.as_ref())),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    // World space bounds of everything renderable, None for an empty scene
+    pub fn get_bounds(&self) -> Option<Bounds> {
+        self.collect(|node, component| match component {
+            Component::Renderable { mesh, .. } => mesh
+                .get_bounds()
+                .map(|bounds| bounds.transform(&node.world)),
+            _ => None,
+        })
+        .into_iter()
+        .reduce(|total, bounds| total.union(&bounds))
+    }
+
+    pub fn directional_lights(&self) -> Vec<DirectionalLight> {
+        self.collect(|node, component| match component {
+            Component::DirectionalLight(light) => Some(DirectionalLight {
+                direction: transform_direction(&node.world, &light.direction),
+                ..light.clone()
+            }),
+            _ => None,
+        })
+    }
+
+    pub fn point_lights(&self) -> Vec<PointLight> {
+        self.collect(|node, component| match component {
+            Component::PointLight(light) => Some(PointLight {
+                position: transform_point(&node.world, &light.position),
+                ..light.clone()
+            }),
+            _ => None,
+        })
+    }
+
+    pub fn spot_lights(&self) -> Vec<SpotLight> {
+        self.collect(|node, component| match component {
+            Component::SpotLight(light) => Some(SpotLight {
+                position: transform_point(&node.world, &light.position),
+                direction: transform_direction(&node.world, &light.direction),
+                ..light.clone()
+            }),
+            _ => None,
+        })
+    }
+
+    pub fn cameras(&self) -> Vec<SceneCamera> {
+        self.collect(|node, component| match component {
+            Component::Camera { y_fov } => Some(SceneCamera {
+                position: transform_point(&node.world, &glm::Vec3::zeros()),
+                front: transform_direction(&node.world, &glm::vec3(0.0, 0.0, -1.0)),
+                y_fov: *y_fov,
+            }),
+            _ => None,
+        })
+    }
+
+    fn collect<T>(&self, mut select: impl FnMut(&Node, &Component) -> Option<T>) -> Vec<T> {
+        self.nodes
+            .iter()
+            .flat_map(|node| {
+                node.components
+                    .iter()
+                    .map(move |component| (node, component))
+            })
+            .filter_map(|(node, component)| select(node, component))
+            .collect()
+    }
+
+    pub fn print_hierarchy(&self, id: NodeId) {
+        self.print_node(id, 1);
+    }
+
+    fn print_node(&self, id: NodeId, depth: usize) {
+        let node = &self.nodes[id];
+        println!("{}{}", "  ".repeat(depth), node.name);
+        for &child in &node.children {
+            self.print_node(child, depth + 1);
+        }
+    }
+}
+
+pub fn create() -> Scene {
+    Scene {
+        nodes: Vec::new(),
+        roots: Vec::new(),
+    }
+}
+
+fn transform_point(matrix: &glm::Mat4, point: &glm::Vec3) -> glm::Vec3 {
+    glm::vec4_to_vec3(&(matrix * glm::vec4(point.x, point.y, point.z, 1.0)))
+}
+
+fn transform_direction(matrix: &glm::Mat4, direction: &glm::Vec3) -> glm::Vec3 {
+    glm::normalize(&(glm::mat4_to_mat3(matrix) * direction))
+}
@@ -0,0 +1,1245 @@
+use crate::camera::{self, Camera, Projection};
+use crate::fly;
+use crate::light::{self, DirectionalLight, PointLight, SpotLight};
+use crate::material::{self, Material};
+use crate::mesh::Mesh;
+use crate::primitive;
+use crate::scene::{self, Component, NodeId, Scene};
+use crate::texture::{self, Texture};
+use crate::{gltf_import, obj};
+use nalgebra_glm as glm;
+use rand::Rng;
+use serde::de::{self, DeserializeSeed, IgnoredAny, Visitor};
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Everything in a scene file is optional; missing fields take the defaults below
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    pub materials: BTreeMap<String, MaterialDescription>,
+    pub cubes: Option<CubeFieldDescription>,
+    pub objects: Vec<ObjectDescription>,
+    pub directional_light: Option<DirectionalLightDescription>,
+    pub point_lights: Vec<PointLightDescription>,
+    pub random_point_lights: Option<RandomPointLightsDescription>,
+    pub flashlight: FlashlightDescription,
+    // Fixes the cube and random light layout, a fresh one is picked when missing
+    pub seed: Option<u64>,
+    // Relative file names are resolved against the scene file's directory
+    #[serde(skip)]
+    directory: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: ProjectionDescription,
+    // Degrees, ignored by orthographic projections
+    pub field_of_view: f32,
+    pub near: f32,
+    // Ignored by the reversed infinite projection
+    pub far: f32,
+    pub movement: MovementDescription,
+}
+
+// See fly::Settings
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct MovementDescription {
+    pub acceleration: f32,
+    pub max_speed: f32,
+    pub damping: f32,
+    pub mouse_smoothing: f32,
+    pub sprint_multiplier: f32,
+}
+
+impl Default for MovementDescription {
+    fn default() -> Self {
+        MovementDescription {
+            acceleration: 40.0,
+            max_speed: 10.0,
+            damping: 8.0,
+            mouse_smoothing: 0.03,
+            sprint_multiplier: 3.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub enum ProjectionDescription {
+    Perspective,
+    Orthographic { height: f32 },
+    ReversedInfinite,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        CameraDescription {
+            position: [0.0, 0.0, 3.0],
+            yaw: -90.0,
+            pitch: 0.0,
+            projection: ProjectionDescription::Perspective,
+            field_of_view: 45.0,
+            near: 0.1,
+            far: 100.0,
+            movement: MovementDescription::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDescription {
+    pub diffuse: TextureDescription,
+    pub specular: TextureDescription,
+    pub shininess: f32,
+}
+
+#[derive(Deserialize)]
+pub enum TextureDescription {
+    Colour(f32, f32, f32),
+    File(PathBuf),
+    // One of the textures compiled into the executable
+    Builtin(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CubeFieldDescription {
+    pub count: usize,
+    // Side length of the volume the cubes are scattered through
+    pub spread: f32,
+    pub material: String,
+    // Radians per second about each cube's random axis
+    pub spin_speed: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectDescription {
+    pub name: String,
+    #[serde(default)]
+    pub mesh: Option<MeshDescription>,
+    // Required for primitives, OBJ and glTF files bring their own materials
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub position: [f32; 3],
+    // Degrees about X, then Y, then Z
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "unit_scale")]
+    pub scale: [f32; 3],
+    #[serde(default)]
+    pub spin: Option<SpinDescription>,
+    #[serde(default)]
+    pub children: Vec<ObjectDescription>,
+}
+
+#[derive(Deserialize)]
+pub enum MeshDescription {
+    Cube,
+    Plane {
+        size: f32,
+        subdivisions: u32,
+    },
+    UvSphere {
+        radius: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Icosphere {
+        radius: f32,
+        subdivisions: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Cone {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+        segments: u32,
+        sides: u32,
+    },
+    Capsule {
+        radius: f32,
+        height: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Obj(PathBuf),
+    Gltf(PathBuf),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpinDescription {
+    pub axis: [f32; 3],
+    // Radians per second
+    pub speed: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectionalLightDescription {
+    pub direction: [f32; 3],
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointLightDescription {
+    pub position: [f32; 3],
+    pub colour: [f32; 3],
+    #[serde(default)]
+    pub attenuation: AttenuationDescription,
+}
+
+// Random lights are only added until the scene has `count` point lights
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RandomPointLightsDescription {
+    pub count: usize,
+    pub spread: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttenuationDescription {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for AttenuationDescription {
+    fn default() -> Self {
+        AttenuationDescription {
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+        }
+    }
+}
+
+// Cutoffs are in degrees
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct FlashlightDescription {
+    pub inner_cutoff: f32,
+    pub outer_cutoff: f32,
+    pub attenuation: AttenuationDescription,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+}
+
+impl Default for FlashlightDescription {
+    fn default() -> Self {
+        FlashlightDescription {
+            inner_cutoff: 12.5,
+            outer_cutoff: 17.5,
+            attenuation: AttenuationDescription {
+                constant: 1.0,
+                linear: 0.07,
+                quadratic: 0.017,
+            },
+            ambient: [0.1, 0.1, 0.1],
+            diffuse: [1.0, 1.0, 1.0],
+            specular: [2.0, 2.0, 2.0],
+        }
+    }
+}
+
+fn unit_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+// A node rotating about a fixed axis on top of its initial rotation
+pub struct Spin {
+    node: NodeId,
+    axis: glm::Vec3,
+    speed: f32,
+    rotation: glm::Quat,
+}
+
+impl Spin {
+    pub fn apply(&self, scene: &mut Scene, seconds: f32) {
+        // Left alone, the node and everything below it keep their world transforms
+        if self.speed == 0.0 {
+            return;
+        }
+        scene.set_rotation(
+            self.node,
+            glm::quat_angle_axis(seconds * self.speed, &self.axis) * self.rotation,
+        );
+    }
+}
+
+pub struct Setup {
+    pub camera: Camera,
+    pub movement: fly::Settings,
+    pub flashlight: SpotLight,
+    pub cube_nodes: Vec<NodeId>,
+    pub cube_material: Option<Rc<Material>>,
+    pub spins: Vec<Spin>,
+}
+
+impl SceneDescription {
+    // Scales the cube field to `count` cubes, spreading them out to keep the
+    // file's density when there are more of them
+    pub fn set_cube_count(&mut self, count: usize) {
+        if let Some(cubes) = &mut self.cubes {
+            if cubes.count > 0 {
+                cubes.spread *= (count as f32 / cubes.count as f32).cbrt().max(1.0);
+            }
+            cubes.count = count;
+        }
+    }
+
+    // Errors are the path to the offending field and what is wrong with it
+    fn validate(&self) -> Result<(), (String, String)> {
+        let camera = &self.camera;
+        check(
+            camera.field_of_view > 0.0 && camera.field_of_view < 180.0,
+            "camera.field_of_view",
+            "must be between 0 and 180 degrees",
+        )?;
+        check(camera.near > 0.0, "camera.near", "must be positive")?;
+        check(
+            camera.far > camera.near,
+            "camera.far",
+            "must be further than near",
+        )?;
+        if let ProjectionDescription::Orthographic { height } = camera.projection {
+            check(height > 0.0, "camera.projection.height", "must be positive")?;
+        }
+        let movement = &camera.movement;
+        for (value, field) in [
+            (movement.acceleration, "acceleration"),
+            (movement.max_speed, "max_speed"),
+            (movement.sprint_multiplier, "sprint_multiplier"),
+        ] {
+            check(
+                value > 0.0,
+                &format!("camera.movement.{}", field),
+                "must be positive",
+            )?;
+        }
+        for (value, field) in [
+            (movement.damping, "damping"),
+            (movement.mouse_smoothing, "mouse_smoothing"),
+        ] {
+            check(
+                value >= 0.0,
+                &format!("camera.movement.{}", field),
+                "must not be negative",
+            )?;
+        }
+
+        for (name, material) in &self.materials {
+            let path = format!("materials[{:?}]", name);
+            validate_texture(&material.diffuse, &format!("{}.diffuse", path))?;
+            validate_texture(&material.specular, &format!("{}.specular", path))?;
+            check(
+                material.shininess > 0.0,
+                &format!("{}.shininess", path),
+                "must be positive",
+            )?;
+        }
+
+        if let Some(cubes) = &self.cubes {
+            check(cubes.spread >= 0.0, "cubes.spread", "must not be negative")?;
+            self.check_material(&cubes.material, "cubes.material")?;
+        }
+
+        for (i, object) in self.objects.iter().enumerate() {
+            self.validate_object(object, &format!("objects[{}]", i))?;
+        }
+
+        if let Some(directional_light) = &self.directional_light {
+            check(
+                glm::length(&glm::Vec3::from(directional_light.direction)) > 0.0,
+                "directional_light.direction",
+                "must not be zero",
+            )?;
+            check_colour(&directional_light.ambient, "directional_light.ambient")?;
+            check_colour(&directional_light.diffuse, "directional_light.diffuse")?;
+            check_colour(&directional_light.specular, "directional_light.specular")?;
+        }
+
+        for (i, point_light) in self.point_lights.iter().enumerate() {
+            let path = format!("point_lights[{}]", i);
+            check_colour(&point_light.colour, &format!("{}.colour", path))?;
+            validate_attenuation(&point_light.attenuation, &format!("{}.attenuation", path))?;
+        }
+        if let Some(random_point_lights) = &self.random_point_lights {
+            check(
+                random_point_lights.count <= light::NUM_POINT_LIGHTS,
+                "random_point_lights.count",
+                &format!("at most {} point lights are lit", light::NUM_POINT_LIGHTS),
+            )?;
+            check(
+                random_point_lights.spread >= 0.0,
+                "random_point_lights.spread",
+                "must not be negative",
+            )?;
+        }
+
+        let flashlight = &self.flashlight;
+        check(
+            flashlight.inner_cutoff > 0.0 && flashlight.inner_cutoff <= flashlight.outer_cutoff,
+            "flashlight.inner_cutoff",
+            "must be positive and no larger than outer_cutoff",
+        )?;
+        check(
+            flashlight.outer_cutoff < 90.0,
+            "flashlight.outer_cutoff",
+            "must be less than 90 degrees",
+        )?;
+        validate_attenuation(&flashlight.attenuation, "flashlight.attenuation")?;
+        check_colour(&flashlight.ambient, "flashlight.ambient")?;
+        check_colour(&flashlight.diffuse, "flashlight.diffuse")?;
+        check_colour(&flashlight.specular, "flashlight.specular")
+    }
+
+    fn validate_object(
+        &self,
+        object: &ObjectDescription,
+        path: &str,
+    ) -> Result<(), (String, String)> {
+        if let Some(mesh) = &object.mesh {
+            validate_mesh(mesh, &format!("{}.mesh", path))?;
+            let has_own_materials =
+                matches!(mesh, MeshDescription::Obj(_) | MeshDescription::Gltf(_));
+            match &object.material {
+                Some(material) => {
+                    check(
+                        !has_own_materials,
+                        &format!("{}.material", path),
+                        "OBJ and glTF meshes use the materials from their files",
+                    )?;
+                    self.check_material(material, &format!("{}.material", path))?;
+                }
+                None => check(
+                    has_own_materials,
+                    &format!("{}.material", path),
+                    "required for primitive meshes",
+                )?,
+            }
+        }
+        check(
+            object.scale.iter().all(|s| *s != 0.0),
+            &format!("{}.scale", path),
+            "must not be zero",
+        )?;
+        if let Some(spin) = &object.spin {
+            check(
+                glm::length(&glm::Vec3::from(spin.axis)) > 0.0,
+                &format!("{}.spin.axis", path),
+                "must not be zero",
+            )?;
+        }
+
+        for (i, child) in object.children.iter().enumerate() {
+            self.validate_object(child, &format!("{}.children[{}]", path, i))?;
+        }
+        Ok(())
+    }
+
+    fn check_material(&self, name: &str, path: &str) -> Result<(), (String, String)> {
+        check(
+            self.materials.contains_key(name),
+            path,
+            &format!("unknown material {:?}", name),
+        )
+    }
+}
+
+pub fn load(path: &Path) -> Result<SceneDescription, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut description = parse(&text, &path.display().to_string())?;
+    description.directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    Ok(description)
+}
+
+pub fn default_scene() -> Result<SceneDescription, String> {
+    parse(include_str!("default_scene.ron"), "default_scene.ron")
+}
+
+// No objects of its own, but lit like the default scene so imported files are visible
+pub fn empty() -> SceneDescription {
+    SceneDescription {
+        directional_light: Some(DirectionalLightDescription {
+            direction: [-0.2, -1.0, -0.3],
+            ambient: [0.05, 0.05, 0.05],
+            diffuse: [0.4, 0.4, 0.4],
+            specular: [0.5, 0.5, 0.5],
+        }),
+        random_point_lights: Some(RandomPointLightsDescription {
+            count: light::NUM_POINT_LIGHTS,
+            spread: 10.0,
+        }),
+        ..SceneDescription::default()
+    }
+}
+
+// Optional fields can be written without Some(...). Errors read
+// "<file>:<line>:<column>: <field path>: <message>"
+fn parse(text: &str, origin: &str) -> Result<SceneDescription, String> {
+    let options =
+        ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME);
+    let mut deserializer = ron::Deserializer::from_str_with_options(text, options.clone())
+        .map_err(|e| format!("{}:{}", origin, e))?;
+    let description: SceneDescription = serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|e| {
+            let path = e.path().to_string();
+            let error = deserializer.span_error(e.into_inner());
+            format!(
+                "{}:{}:{}: {}: {}",
+                origin, error.position.line, error.position.col, path, error.code
+            )
+        })?;
+    deserializer.end().map_err(|e| {
+        let error = deserializer.span_error(e);
+        format!(
+            "{}:{}:{}: {}",
+            origin, error.position.line, error.position.col, error.code
+        )
+    })?;
+
+    description.validate().map_err(|(path, message)| {
+        match locate(text, &options, &split_path(&path)) {
+            Some(position) => format!(
+                "{}:{}:{}: {}: {}",
+                origin, position.line, position.col, path, message
+            ),
+            None => format!("{}: {}: {}", origin, path, message),
+        }
+    })?;
+    Ok(description)
+}
+
+// Where the value at a field path starts in the file. Validation runs on the
+// parsed description, so the file is walked again up to that value. Fields
+// left out take their defaults, in which case the nearest enclosing one that
+// was written is given instead.
+fn locate(text: &str, options: &ron::Options, path: &[String]) -> Option<ron::error::Position> {
+    (0..=path.len()).rev().find_map(|length| {
+        let mut deserializer =
+            ron::Deserializer::from_str_with_options(text, options.clone()).ok()?;
+        let found = Cell::new(false);
+        let error = Locator {
+            target: Some(&path[..length]),
+            found: &found,
+        }
+        .deserialize(&mut deserializer)
+        .err()?;
+        found.get().then(|| deserializer.span_error(error).position)
+    })
+}
+
+// "objects[1].mesh.radius" or "materials[\"wood\"].diffuse" as the names,
+// indices and keys along the way
+fn split_path(path: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (segment, after) = if let Some(key) = rest.strip_prefix("[\"") {
+            key.split_once("\"]").unwrap_or((key, ""))
+        } else if let Some(index) = rest.strip_prefix('[') {
+            index.split_once(']').unwrap_or((index, ""))
+        } else {
+            let name = rest.strip_prefix('.').unwrap_or(rest);
+            name.split_at(name.find(['.', '[']).unwrap_or(name.len()))
+        };
+        segments.push(segment.to_string());
+        rest = after;
+    }
+    segments
+}
+
+// Walks any value, failing as soon as it reaches the end of `target`. Values
+// off the path have no target and are skipped.
+struct Locator<'a> {
+    target: Option<&'a [String]>,
+    found: &'a Cell<bool>,
+}
+
+impl<'a> Locator<'a> {
+    fn child(&self, segment: &str) -> Locator<'a> {
+        Locator {
+            target: self
+                .target
+                .and_then(|target| target.split_first())
+                .filter(|(first, _)| *first == segment)
+                .map(|(_, rest)| rest),
+            found: self.found,
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Locator<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.target {
+            Some([]) => {
+                self.found.set(true);
+                Err(de::Error::custom("found"))
+            }
+            Some(_) => deserializer.deserialize_any(self),
+            None => deserializer.deserialize_ignored_any(IgnoredAny).map(|_| ()),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Locator<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0;
+        while seq
+            .next_element_seed(self.child(&index.to_string()))?
+            .is_some()
+        {
+            index += 1;
+        }
+        Ok(())
+    }
+
+    // Struct fields and map keys alike
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key_seed(Key)? {
+            map.next_value_seed(self.child(&key))?;
+        }
+        Ok(())
+    }
+}
+
+// Struct fields are identifiers and map keys strings, either is read as a string
+struct Key;
+
+impl<'de> DeserializeSeed<'de> for Key {
+    type Value = String;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Key {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a field name or map key")
+    }
+
+    fn visit_str<E>(self, key: &str) -> Result<String, E> {
+        Ok(key.to_string())
+    }
+}
+
+// Objects are added below whatever is already in `scene`, random point lights
+// only top up the lights it already has
+pub fn build(
+    description: &SceneDescription,
+    scene: &mut Scene,
+    cube: &Rc<Mesh>,
+    rng: &mut impl Rng,
+) -> Result<Setup, String> {
+    let materials = description
+        .materials
+        .iter()
+        .map(|(name, material)| {
+            Ok((
+                name.as_str(),
+                Rc::new(material::create(
+                    Rc::new(create_texture(&material.diffuse, &description.directory)?),
+                    Rc::new(create_texture(&material.specular, &description.directory)?),
+                    material.shininess,
+                )),
+            ))
+        })
+        .collect::<Result<BTreeMap<&str, Rc<Material>>, String>>()?;
+
+    let mut spins = Vec::new();
+    let mut builder = Builder {
+        directory: &description.directory,
+        materials: &materials,
+        cube,
+        spins: &mut spins,
+    };
+    for object in &description.objects {
+        builder.add_object(scene, object, None)?;
+    }
+
+    let mut cube_nodes = Vec::new();
+    let mut cube_material = None;
+    if let Some(cubes) = &description.cubes {
+        let field = scene.add_node("cubes", None, scene::identity());
+        for _ in 0..cubes.count {
+            let position = random_vector(rng, cubes.spread);
+            let axis = glm::normalize(&random_vector(rng, 1.0));
+            let node = scene.add_node("cube", Some(field), scene::translation(position));
+            cube_nodes.push(node);
+            spins.push(Spin {
+                node,
+                axis,
+                speed: cubes.spin_speed,
+                rotation: glm::quat_identity(),
+            });
+        }
+        cube_material = Some(materials[cubes.material.as_str()].clone());
+    }
+
+    if let Some(directional_light) = &description.directional_light {
+        let node = scene.add_node("directional light", None, scene::identity());
+        scene.add_component(
+            node,
+            Component::DirectionalLight(DirectionalLight {
+                direction: glm::normalize(&glm::Vec3::from(directional_light.direction)),
+                ambient: glm::Vec3::from(directional_light.ambient),
+                diffuse: glm::Vec3::from(directional_light.diffuse),
+                specular: glm::Vec3::from(directional_light.specular),
+            }),
+        );
+    }
+
+    for point_light in &description.point_lights {
+        add_point_light(
+            scene,
+            glm::Vec3::from(point_light.position),
+            PointLight {
+                colour: glm::Vec3::from(point_light.colour),
+                attenuation_constant: point_light.attenuation.constant,
+                attenuation_linear: point_light.attenuation.linear,
+                attenuation_quadratic: point_light.attenuation.quadratic,
+                ..light::point(glm::Vec3::zeros(), glm::Vec3::zeros(), false)
+            },
+        );
+    }
+    if let Some(random_point_lights) = &description.random_point_lights {
+        let existing = scene.point_lights().len();
+        for _ in existing..random_point_lights.count {
+            let position = random_vector(rng, random_point_lights.spread);
+            let colour = random_colour(rng);
+            add_point_light(
+                scene,
+                position,
+                light::point(glm::Vec3::zeros(), colour, false),
+            );
+        }
+    }
+
+    let start = &description.camera;
+    let flashlight = &description.flashlight;
+    let position = glm::Vec3::from(start.position);
+    let y_fov = num::Float::to_radians(start.field_of_view);
+    let projection = match start.projection {
+        ProjectionDescription::Perspective => Projection::Perspective {
+            y_fov,
+            near: start.near,
+            far: start.far,
+        },
+        ProjectionDescription::Orthographic { height } => Projection::Orthographic {
+            height,
+            near: start.near,
+            far: start.far,
+        },
+        ProjectionDescription::ReversedInfinite => Projection::ReversedInfinite {
+            y_fov,
+            near: start.near,
+        },
+    };
+    let camera = camera::create(position, start.yaw, start.pitch, projection);
+    Ok(Setup {
+        flashlight: SpotLight {
+            position,
+            direction: camera.get_front(),
+            inner_cutoff: flashlight.inner_cutoff,
+            outer_cutoff: flashlight.outer_cutoff,
+            attenuation_constant: flashlight.attenuation.constant,
+            attenuation_linear: flashlight.attenuation.linear,
+            attenuation_quadratic: flashlight.attenuation.quadratic,
+            ambient: glm::Vec3::from(flashlight.ambient),
+            diffuse: glm::Vec3::from(flashlight.diffuse),
+            specular: glm::Vec3::from(flashlight.specular),
+        },
+        camera,
+        movement: fly::Settings {
+            acceleration: start.movement.acceleration,
+            max_speed: start.movement.max_speed,
+            damping: start.movement.damping,
+            mouse_smoothing: start.movement.mouse_smoothing,
+            sprint_multiplier: start.movement.sprint_multiplier,
+        },
+        cube_nodes,
+        cube_material,
+        spins,
+    })
+}
+
+struct Builder<'a> {
+    directory: &'a Path,
+    materials: &'a BTreeMap<&'a str, Rc<Material>>,
+    cube: &'a Rc<Mesh>,
+    spins: &'a mut Vec<Spin>,
+}
+
+impl Builder<'_> {
+    fn add_object(
+        &mut self,
+        scene: &mut Scene,
+        object: &ObjectDescription,
+        parent: Option<NodeId>,
+    ) -> Result<(), String> {
+        let [x, y, z] = object.rotation.map(num::Float::to_radians);
+        let rotation = glm::quat_angle_axis(z, &glm::vec3(0.0, 0.0, 1.0))
+            * glm::quat_angle_axis(y, &glm::vec3(0.0, 1.0, 0.0))
+            * glm::quat_angle_axis(x, &glm::vec3(1.0, 0.0, 0.0));
+        let node = scene.add_node(
+            &object.name,
+            parent,
+            scene::Transform {
+                translation: glm::Vec3::from(object.position),
+                rotation,
+                scale: glm::Vec3::from(object.scale),
+            },
+        );
+
+        if let Some(mesh) = &object.mesh {
+            self.add_mesh(scene, node, mesh, object.material.as_deref())?;
+        }
+        if let Some(spin) = &object.spin {
+            self.spins.push(Spin {
+                node,
+                axis: glm::normalize(&glm::Vec3::from(spin.axis)),
+                speed: spin.speed,
+                rotation,
+            });
+        }
+
+        for child in &object.children {
+            self.add_object(scene, child, Some(node))?;
+        }
+        Ok(())
+    }
+
+    fn add_mesh(
+        &self,
+        scene: &mut Scene,
+        node: NodeId,
+        mesh: &MeshDescription,
+        material: Option<&str>,
+    ) -> Result<(), String> {
+        let geometry = match *mesh {
+            MeshDescription::Cube => None,
+            MeshDescription::Plane { size, subdivisions } => {
+                Some(primitive::plane(size, subdivisions))
+            }
+            MeshDescription::UvSphere {
+                radius,
+                segments,
+                rings,
+            } => Some(primitive::uv_sphere(radius, segments, rings)),
+            MeshDescription::Icosphere {
+                radius,
+                subdivisions,
+            } => Some(primitive::icosphere(radius, subdivisions)),
+            MeshDescription::Cylinder {
+                radius,
+                height,
+                segments,
+            } => Some(primitive::cylinder(radius, height, segments)),
+            MeshDescription::Cone {
+                radius,
+                height,
+                segments,
+            } => Some(primitive::cone(radius, height, segments)),
+            MeshDescription::Torus {
+                major_radius,
+                minor_radius,
+                segments,
+                sides,
+            } => Some(primitive::torus(
+                major_radius,
+                minor_radius,
+                segments,
+                sides,
+            )),
+            MeshDescription::Capsule {
+                radius,
+                height,
+                segments,
+                rings,
+            } => Some(primitive::capsule(radius, height, segments, rings)),
+            MeshDescription::Obj(ref path) => {
+                let model = obj::load(&self.directory.join(path))?;
+                for part in model.get_parts() {
+                    let part_node = scene.add_node(&part.name, Some(node), scene::identity());
+                    scene.add_component(
+                        part_node,
+                        Component::Renderable {
+                            mesh: part.mesh.clone(),
+                            material: part.material.clone(),
+                        },
+                    );
+                }
+                return Ok(());
+            }
+            MeshDescription::Gltf(ref path) => {
+                gltf_import::load(&self.directory.join(path), scene, Some(node))?;
+                return Ok(());
+            }
+        };
+
+        let mesh = match geometry {
+            Some(geometry) => Rc::new(geometry.upload()?),
+            None => self.cube.clone(),
+        };
+        // Validation guarantees primitives name one of the file's materials
+        let material = self.materials[material.unwrap_or_default()].clone();
+        scene.add_component(node, Component::Renderable { mesh, material });
+        Ok(())
+    }
+}
+
+fn add_point_light(scene: &mut Scene, position: glm::Vec3, point_light: PointLight) {
+    let node = scene.add_node("point light", None, scene::translation(position));
+    scene.add_component(node, Component::PointLight(point_light));
+}
+
+fn create_texture(description: &TextureDescription, directory: &Path) -> Result<Texture, String> {
+    match description {
+        TextureDescription::Colour(r, g, b) => texture::from_colour([*r, *g, *b]),
+        TextureDescription::File(file_name) => {
+            let path = directory.join(file_name);
+            let data = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            texture::create(&data)
+        }
+        TextureDescription::Builtin(name) => {
+            texture::create(builtin_texture(name).unwrap_or_default())
+        }
+    }
+}
+
+fn builtin_texture(name: &str) -> Option<&'static [u8]> {
+    match name {
+        "wood_steel_border" => Some(include_bytes!("wood_steel_border.png")),
+        "steel_border" => Some(include_bytes!("steel_border.png")),
+        _ => None,
+    }
+}
+
+fn validate_texture(texture: &TextureDescription, path: &str) -> Result<(), (String, String)> {
+    match texture {
+        TextureDescription::Colour(r, g, b) => check_colour(&[*r, *g, *b], path),
+        TextureDescription::File(_) => Ok(()),
+        TextureDescription::Builtin(name) => check(
+            builtin_texture(name).is_some(),
+            path,
+            &format!("unknown builtin texture {:?}", name),
+        ),
+    }
+}
+
+fn validate_mesh(mesh: &MeshDescription, path: &str) -> Result<(), (String, String)> {
+    let positive = |value: f32, field: &str| {
+        check(
+            value > 0.0,
+            &format!("{}.{}", path, field),
+            "must be positive",
+        )
+    };
+    let at_least = |value: u32, minimum: u32, field: &str| {
+        check(
+            value >= minimum,
+            &format!("{}.{}", path, field),
+            &format!("must be at least {}", minimum),
+        )
+    };
+    match *mesh {
+        MeshDescription::Cube | MeshDescription::Obj(_) | MeshDescription::Gltf(_) => Ok(()),
+        MeshDescription::Plane { size, subdivisions } => {
+            positive(size, "size")?;
+            at_least(subdivisions, 1, "subdivisions")
+        }
+        MeshDescription::UvSphere {
+            radius,
+            segments,
+            rings,
+        } => {
+            positive(radius, "radius")?;
+            at_least(segments, 3, "segments")?;
+            at_least(rings, 2, "rings")
+        }
+        MeshDescription::Icosphere { radius, .. } => positive(radius, "radius"),
+        MeshDescription::Cylinder {
+            radius,
+            height,
+            segments,
+        }
+        | MeshDescription::Cone {
+            radius,
+            height,
+            segments,
+        } => {
+            positive(radius, "radius")?;
+            positive(height, "height")?;
+            at_least(segments, 3, "segments")
+        }
+        MeshDescription::Torus {
+            major_radius,
+            minor_radius,
+            segments,
+            sides,
+        } => {
+            positive(major_radius, "major_radius")?;
+            positive(minor_radius, "minor_radius")?;
+            at_least(segments, 3, "segments")?;
+            at_least(sides, 3, "sides")
+        }
+        MeshDescription::Capsule {
+            radius,
+            height,
+            segments,
+            rings,
+        } => {
+            positive(radius, "radius")?;
+            check(
+                height >= 0.0,
+                &format!("{}.height", path),
+                "must not be negative",
+            )?;
+            at_least(segments, 3, "segments")?;
+            at_least(rings, 1, "rings")
+        }
+    }
+}
+
+fn validate_attenuation(
+    attenuation: &AttenuationDescription,
+    path: &str,
+) -> Result<(), (String, String)> {
+    check(
+        attenuation.constant > 0.0,
+        &format!("{}.constant", path),
+        "must be positive",
+    )?;
+    check(
+        attenuation.linear >= 0.0,
+        &format!("{}.linear", path),
+        "must not be negative",
+    )?;
+    check(
+        attenuation.quadratic >= 0.0,
+        &format!("{}.quadratic", path),
+        "must not be negative",
+    )
+}
+
+fn check_colour(colour: &[f32; 3], path: &str) -> Result<(), (String, String)> {
+    check(
+        colour.iter().all(|channel| *channel >= 0.0),
+        path,
+        "colour channels must not be negative",
+    )
+}
+
+fn check(condition: bool, path: &str, message: &str) -> Result<(), (String, String)> {
+    if condition {
+        Ok(())
+    } else {
+        Err((path.to_string(), message.to_string()))
+    }
+}
+
+// Uniformly inside a cube of side `size` centred on the origin
+fn random_vector(rng: &mut impl Rng, size: f32) -> glm::Vec3 {
+    glm::vec3(
+        size * rng.gen::<f32>() - (size / 2.0),
+        size * rng.gen::<f32>() - (size / 2.0),
+        size * rng.gen::<f32>() - (size / 2.0),
+    )
+}
+
+fn random_colour(rng: &mut impl Rng) -> glm::Vec3 {
+    let colour = glm::vec3(
+        rng.gen_range(0.1..1.0),
+        rng.gen_range(0.1..1.0),
+        rng.gen_range(0.1..1.0),
+    );
+    colour / colour.max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATERIALS: &str = r#"
+    materials: {
+        "red": (
+            diffuse: Colour(1.0, 0.0, 0.0),
+            specular: Colour(0.5, 0.5, 0.5),
+            shininess: 32.0,
+        ),
+    },"#;
+
+    fn parse_scene(body: &str) -> Result<SceneDescription, String> {
+        parse(&format!("({}\n{}\n)", MATERIALS, body), "test.ron")
+    }
+
+    #[test]
+    fn default_scene_is_valid() {
+        let description = default_scene().unwrap();
+        assert!(description.cubes.is_some());
+        assert!(!description.objects.is_empty());
+    }
+
+    #[test]
+    fn valid_scene_is_read() {
+        let description = parse_scene(
+            r#"
+    camera: (position: (1.0, 2.0, 3.0), projection: Orthographic(height: 8.0)),
+    objects: [
+        (name: "ball", mesh: UvSphere(radius: 0.5, segments: 16, rings: 8), material: "red"),
+    ],
+    seed: 7,"#,
+        )
+        .unwrap();
+        assert_eq!(description.camera.position, [1.0, 2.0, 3.0]);
+        assert!(matches!(
+            description.camera.projection,
+            ProjectionDescription::Orthographic { height } if height == 8.0
+        ));
+        assert_eq!(description.objects[0].name, "ball");
+        assert_eq!(description.objects[0].material.as_deref(), Some("red"));
+        assert_eq!(description.seed, Some(7));
+        // Left out, so at its default
+        assert_eq!(description.camera.yaw, -90.0);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let error = parse_scene(
+            r#"
+    camera: (position: (0.0, 0.0, 3.0), zoom: 2.0),"#,
+        )
+        .err()
+        .unwrap();
+        assert!(
+            error.starts_with("test.ron:10:") && error.contains("camera.zoom"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn semantic_errors_point_at_the_field() {
+        let error = parse_scene(
+            r#"
+    objects: [
+        (name: "ball", mesh: UvSphere(radius: 0.5, segments: 16, rings: 8), material: "red"),
+        (
+            name: "disc",
+            mesh: Cylinder(radius: -1.0, height: 0.1, segments: 32),
+            material: "red",
+        ),
+    ],"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error,
+            "test.ron:14:36: objects[1].mesh.radius: must be positive"
+        );
+
+        let error = parse_scene(
+            r#"
+    cubes: (count: 10, spread: 5.0, material: "blue", spin_speed: 1.0),"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error,
+            r#"test.ron:10:47: cubes.material: unknown material "blue""#
+        );
+    }
+
+    #[test]
+    fn defaulted_fields_point_at_their_parent() {
+        // far is left at its default of 100, closer than near
+        let error = parse_scene("    camera: (near: 200.0),").err().unwrap();
+        assert_eq!(
+            error,
+            "test.ron:9:13: camera.far: must be further than near"
+        );
+    }
+
+    #[test]
+    fn paths_are_split_into_segments() {
+        assert_eq!(
+            split_path(r#"objects[1].children[0].mesh.radius"#),
+            ["objects", "1", "children", "0", "mesh", "radius"]
+        );
+        assert_eq!(
+            split_path(r#"materials["wood floor"].diffuse"#),
+            ["materials", "wood floor", "diffuse"]
+        );
+    }
+}
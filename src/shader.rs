@@ -37,6 +37,19 @@ impl Shader {
         Ok(())
     }
 
+    pub fn set_mat3(&self, name: &str, mat: &nalgebra_glm::Mat3) -> Result<(), String> {
+        let loc = find_uniform(self.id, name)?;
+        unsafe {
+            gl::UniformMatrix3fv(
+                loc as i32,
+                1,
+                gl::FALSE,
+                nalgebra_glm::value_ptr(&mat).as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
     pub fn set_mat4(&self, name: &str, mat: &nalgebra_glm::Mat4) -> Result<(), String> {
         let loc = find_uniform(self.id, name)?;
         unsafe {
@@ -44,7 +57,7 @@ impl Shader {
                 loc as i32,
                 1,
                 gl::FALSE,
-                nalgebra_glm::value_ptr(&mat).as_ptr(),
+                nalgebra_glm::value_ptr(mat).as_ptr(),
             );
         }
         Ok(())
@@ -60,7 +73,15 @@ impl Drop for Shader {
 }
 
 pub fn compile_from_sources(vertex_source: &str, fragment_source: &str) -> Result<Shader, String> {
-    program_id_from_shaders(&vertex_source, &fragment_source)
+    program_id_from_shaders(&vertex_source, None, &fragment_source)
+}
+
+pub fn compile_from_sources_with_geometry(
+    vertex_source: &str,
+    geometry_source: &str,
+    fragment_source: &str,
+) -> Result<Shader, String> {
+    program_id_from_shaders(vertex_source, Some(geometry_source), fragment_source)
 }
 
 fn find_uniform(program_id: GLuint, name: &str) -> Result<GLuint, String> {
@@ -123,15 +144,26 @@ fn compile_shader(source: &str, kind: GLuint) -> Result<ShaderComponent, String>
     Ok(shader_component)
 }
 
-fn program_id_from_shaders(vertex_source: &str, fragment_source: &str) -> Result<Shader, String> {
+fn program_id_from_shaders(
+    vertex_source: &str,
+    geometry_source: Option<&str>,
+    fragment_source: &str,
+) -> Result<Shader, String> {
     let shader = Shader {
         id: unsafe { gl::CreateProgram() },
     };
 
     let vertex_component = compile_shader(&vertex_source, gl::VERTEX_SHADER)?;
+    let geometry_component = match geometry_source {
+        Some(source) => Some(compile_shader(source, gl::GEOMETRY_SHADER)?),
+        None => None,
+    };
     let fragment_component = compile_shader(&fragment_source, gl::FRAGMENT_SHADER)?;
 
     let _vertex_link = ShaderLink::create(shader.id, vertex_component.id);
+    let _geometry_link = geometry_component
+        .as_ref()
+        .map(|component| ShaderLink::create(shader.id, component.id));
     let _fragment_link = ShaderLink::create(shader.id, fragment_component.id);
 
     let mut success: GLint = 0;
@@ -0,0 +1,452 @@
+extern crate gl;
+use crate::camera::Projection;
+use crate::light::{SpotLight, NUM_POINT_LIGHTS};
+use crate::shader::Shader;
+use gl::types::*;
+use nalgebra_glm as glm;
+
+pub const MAX_CASCADES: usize = 4;
+
+pub struct ShadowMap {
+    id: GLuint,
+    depth: GLuint,
+    resolution: u32,
+    layers: u32,
+}
+
+impl ShadowMap {
+    pub fn bind_layer(&self, layer: u32) -> Result<(), String> {
+        if layer >= self.layers {
+            return Err(format!(
+                "Shadow map layer {} out of range ({} layers)",
+                layer, self.layers
+            ));
+        }
+        let resolution: GLsizei = self.resolution.try_into().map_err(error_to_string())?;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                self.depth,
+                0,
+                layer as GLint,
+            );
+            gl::Viewport(0, 0, resolution, resolution);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        Ok(())
+    }
+
+    pub fn bind_depth_texture(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.depth);
+        }
+    }
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.depth);
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+pub fn create(resolution: u32, layers: u32) -> Result<ShadowMap, String> {
+    let size: GLsizei = resolution.try_into().map_err(error_to_string())?;
+    let depth: GLsizei = layers.try_into().map_err(error_to_string())?;
+
+    let mut shadow_map = ShadowMap {
+        id: 0,
+        depth: 0,
+        resolution,
+        layers,
+    };
+    unsafe {
+        gl::GenFramebuffers(1, &mut shadow_map.id);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_map.id);
+
+        gl::GenTextures(1, &mut shadow_map.depth);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, shadow_map.depth);
+        gl::TexImage3D(
+            gl::TEXTURE_2D_ARRAY,
+            0,
+            gl::DEPTH_COMPONENT24 as i32,
+            size,
+            size,
+            depth,
+            0,
+            gl::DEPTH_COMPONENT,
+            gl::FLOAT,
+            std::ptr::null(),
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MIN_FILTER,
+            gl::NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MAG_FILTER,
+            gl::NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_WRAP_S,
+            gl::CLAMP_TO_BORDER as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_BORDER as i32,
+        );
+        let border = [1.0_f32, 1.0, 1.0, 1.0];
+        gl::TexParameterfv(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_BORDER_COLOR,
+            border.as_ptr(),
+        );
+
+        gl::FramebufferTextureLayer(
+            gl::FRAMEBUFFER,
+            gl::DEPTH_ATTACHMENT,
+            shadow_map.depth,
+            0,
+            0,
+        );
+        gl::DrawBuffer(gl::NONE);
+        gl::ReadBuffer(gl::NONE);
+    }
+
+    let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(format!(
+            "Shadow map framebuffer is incomplete: 0x{:x}",
+            status
+        ));
+    }
+
+    Ok(shadow_map)
+}
+
+pub struct CubeShadowMap {
+    id: GLuint,
+    depth: GLuint,
+    resolution: u32,
+}
+
+impl CubeShadowMap {
+    pub fn bind(&self) -> Result<(), String> {
+        let resolution: GLsizei = self.resolution.try_into().map_err(error_to_string())?;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, resolution, resolution);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        Ok(())
+    }
+
+    pub fn bind_depth_texture(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.depth);
+        }
+    }
+}
+
+impl Drop for CubeShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.depth);
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+pub fn create_cube(resolution: u32) -> Result<CubeShadowMap, String> {
+    let size: GLsizei = resolution.try_into().map_err(error_to_string())?;
+
+    let mut shadow_map = CubeShadowMap {
+        id: 0,
+        depth: 0,
+        resolution,
+    };
+    unsafe {
+        gl::GenFramebuffers(1, &mut shadow_map.id);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_map.id);
+
+        gl::GenTextures(1, &mut shadow_map.depth);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, shadow_map.depth);
+        for face in 0..6 {
+            gl::TexImage2D(
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                0,
+                gl::DEPTH_COMPONENT24 as i32,
+                size,
+                size,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+        }
+        gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_MIN_FILTER,
+            gl::NEAREST as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_MAG_FILTER,
+            gl::NEAREST as i32,
+        );
+        for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, wrap, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        // Attaching the whole cube map makes the framebuffer layered, so the
+        // geometry shader can route each triangle to a face with gl_Layer
+        gl::FramebufferTexture(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, shadow_map.depth, 0);
+        gl::DrawBuffer(gl::NONE);
+        gl::ReadBuffer(gl::NONE);
+    }
+
+    let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(format!(
+            "Cube shadow map framebuffer is incomplete: 0x{:x}",
+            status
+        ));
+    }
+
+    Ok(shadow_map)
+}
+
+pub struct PointSettings {
+    pub resolution: u32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl PointSettings {
+    fn face_matrices(&self, position: &glm::Vec3) -> [glm::Mat4; 6] {
+        let projection = glm::perspective(1.0, num::Float::to_radians(90.0), self.near, self.far);
+        let face = |direction: glm::Vec3, up: glm::Vec3| {
+            projection * glm::look_at(position, &(position + direction), &up)
+        };
+        [
+            face(glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+            face(glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+            face(glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+            face(glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+            face(glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, -1.0, 0.0)),
+            face(glm::vec3(0.0, 0.0, -1.0), glm::vec3(0.0, -1.0, 0.0)),
+        ]
+    }
+
+    pub fn apply_depth(&self, shader: &Shader, position: &glm::Vec3) -> Result<(), String> {
+        for (face, matrix) in self.face_matrices(position).iter().enumerate() {
+            shader.set_mat4(format!("uShadowMatrices[{}]", face).as_str(), matrix)?;
+        }
+        shader.set_vec3("uLightPos", position)?;
+        shader.set_float("uFarPlane", self.far)?;
+        Ok(())
+    }
+
+    pub fn apply(&self, shader: &Shader, first_unit: i32) -> Result<(), String> {
+        // Every samplerCube needs its own unit, even for lights without shadows
+        for i in 0..NUM_POINT_LIGHTS {
+            shader.set_int(
+                format!("uPointShadowMaps[{}]", i).as_str(),
+                first_unit + i as i32,
+            )?;
+        }
+        shader.set_float("uPointShadowFar", self.far)?;
+        Ok(())
+    }
+}
+
+pub struct SpotSettings {
+    pub resolution: u32,
+    pub near: f32,
+    pub far: f32,
+    pub light_size: f32,
+    pub bias: f32,
+}
+
+impl SpotSettings {
+    pub fn light_space_matrix(&self, light: &SpotLight) -> glm::Mat4 {
+        // Cover the outer cone with a small margin so the PCSS kernel stays inside the map,
+        // short of 180° where the projection degenerates
+        let fov = num::Float::to_radians((2.0 * light.outer_cutoff + 5.0).min(170.0));
+        let projection = glm::perspective(1.0, fov, self.near, self.far);
+        let view = glm::look_at(
+            &light.position,
+            &(light.position + light.direction),
+            &up_for(&light.direction),
+        );
+        projection * view
+    }
+
+    pub fn apply(&self, shader: &Shader, light_space: &glm::Mat4) -> Result<(), String> {
+        shader.set_mat4("uSpotLightSpace", light_space)?;
+        shader.set_float("uSpotShadowNear", self.near)?;
+        shader.set_float("uSpotShadowFar", self.far)?;
+        shader.set_float("uSpotLightSize", self.light_size)?;
+        shader.set_float("uSpotShadowBias", self.bias)?;
+        Ok(())
+    }
+}
+
+pub struct ViewFrustum {
+    pub view: glm::Mat4,
+    pub projection: Projection,
+    pub aspect: f32,
+}
+
+pub struct Cascades {
+    pub light_spaces: Vec<glm::Mat4>,
+    pub splits: Vec<f32>,
+}
+
+pub struct Settings {
+    pub resolution: u32,
+    pub cascade_count: usize,
+    pub split_lambda: f32,
+    pub max_distance: f32,
+    pub caster_extension: f32,
+    pub blend_fraction: f32,
+    pub pcf_radius: i32,
+    pub bias_constant: f32,
+    pub bias_slope: f32,
+}
+
+impl Settings {
+    fn split_distances(&self, near: f32, far: f32) -> Vec<f32> {
+        // Practical split scheme: a blend of logarithmic and uniform splits
+        let far = far.min(self.max_distance);
+        let count = self.cascade_count.clamp(1, MAX_CASCADES);
+        (1..=count)
+            .map(|i| {
+                let fraction = i as f32 / count as f32;
+                let logarithmic = near * (far / near).powf(fraction);
+                let uniform = near + (far - near) * fraction;
+                self.split_lambda * logarithmic + (1.0 - self.split_lambda) * uniform
+            })
+            .collect()
+    }
+
+    pub fn fit_cascades(&self, direction: &glm::Vec3, frustum: &ViewFrustum) -> Cascades {
+        let (near, far) = frustum.projection.depth_range();
+        let splits = self.split_distances(near, far);
+        let light_spaces = splits
+            .iter()
+            .enumerate()
+            .map(|(i, &split_far)| {
+                let split_near = if i == 0 { near } else { splits[i - 1] };
+                self.fit_cascade(direction, frustum, split_near, split_far)
+            })
+            .collect();
+        Cascades {
+            light_spaces,
+            splits,
+        }
+    }
+
+    fn fit_cascade(
+        &self,
+        direction: &glm::Vec3,
+        frustum: &ViewFrustum,
+        split_near: f32,
+        split_far: f32,
+    ) -> glm::Mat4 {
+        let projection = frustum
+            .projection
+            .clipped(frustum.aspect, split_near, split_far);
+        let corners = frustum_corners(&(projection * frustum.view));
+
+        // A bounding sphere keeps the cascade size constant as the camera rotates
+        let centre = corners.iter().fold(glm::Vec3::zeros(), |sum, c| sum + c) / 8.0;
+        let radius = corners
+            .iter()
+            .map(|corner| glm::distance(corner, &centre))
+            .fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let light_view = glm::look_at(
+            &glm::Vec3::zeros(),
+            &glm::normalize(direction),
+            &up_for(direction),
+        );
+
+        // Snap to whole texels so the map does not shimmer as the camera moves
+        let texel = 2.0 * radius / self.resolution as f32;
+        let centre = light_view * glm::vec4(centre.x, centre.y, centre.z, 1.0);
+        let x = (centre.x / texel).floor() * texel;
+        let y = (centre.y / texel).floor() * texel;
+
+        let projection = glm::ortho(
+            x - radius,
+            x + radius,
+            y - radius,
+            y + radius,
+            -centre.z - radius - self.caster_extension,
+            -centre.z + radius,
+        );
+        projection * light_view
+    }
+
+    pub fn apply(&self, shader: &Shader, cascades: &Cascades) -> Result<(), String> {
+        for (i, (light_space, split)) in cascades
+            .light_spaces
+            .iter()
+            .zip(&cascades.splits)
+            .enumerate()
+        {
+            shader.set_mat4(format!("uLightSpaces[{}]", i).as_str(), light_space)?;
+            shader.set_float(format!("uCascadeSplits[{}]", i).as_str(), *split)?;
+        }
+        shader.set_int("uCascadeCount", cascades.splits.len() as i32)?;
+        shader.set_float("uCascadeBlend", self.blend_fraction)?;
+        shader.set_int("uShadowPcfRadius", self.pcf_radius)?;
+        shader.set_float("uShadowBiasConstant", self.bias_constant)?;
+        shader.set_float("uShadowBiasSlope", self.bias_slope)?;
+        Ok(())
+    }
+}
+
+fn frustum_corners(view_projection: &glm::Mat4) -> Vec<glm::Vec3> {
+    let inverse = glm::inverse(view_projection);
+    let mut corners = Vec::with_capacity(8);
+    for x in [-1.0, 1.0] {
+        for y in [-1.0, 1.0] {
+            for z in [-1.0, 1.0] {
+                let corner = inverse * glm::vec4(x, y, z, 1.0);
+                corners.push(corner.xyz() / corner.w);
+            }
+        }
+    }
+    corners
+}
+
+fn up_for(direction: &glm::Vec3) -> glm::Vec3 {
+    if glm::normalize(direction).y.abs() > 0.99 {
+        glm::vec3(0.0, 0.0, 1.0)
+    } else {
+        glm::vec3(0.0, 1.0, 0.0)
+    }
+}
+
+fn error_to_string<E>() -> fn(E) -> String
+where
+    E: std::fmt::Display,
+{
+    |e: E| e.to_string()
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+uniform sampler2D uImage;
+uniform float uStrength;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    vec2 texel = 1.0 / vec2(textureSize(uImage, 0));
+
+    vec3 centre = texture(uImage, aTextureCoords).rgb;
+    vec3 neighbours = texture(uImage, aTextureCoords + vec2(texel.x, 0.0)).rgb
+        + texture(uImage, aTextureCoords - vec2(texel.x, 0.0)).rgb
+        + texture(uImage, aTextureCoords + vec2(0.0, texel.y)).rgb
+        + texture(uImage, aTextureCoords - vec2(0.0, texel.y)).rgb;
+
+    vec3 sharpened = centre + uStrength * (4.0 * centre - neighbours);
+    aFragColours = vec4(max(sharpened, vec3(0.0)), 1.0);
+}
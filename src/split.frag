@@ -0,0 +1,20 @@
+#version 330 core
+
+uniform sampler2D uScene;
+uniform sampler2D uImage;
+uniform float uSplit;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    if (abs(aTextureCoords.x - uSplit) < 0.001) {
+        aFragColours = vec4(1.0);
+    } else if (aTextureCoords.x < uSplit) {
+        aFragColours = vec4(texture(uScene, aTextureCoords).rgb, 1.0);
+    } else {
+        aFragColours = vec4(texture(uImage, aTextureCoords).rgb, 1.0);
+    }
+}
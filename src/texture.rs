@@ -22,8 +22,18 @@ impl Drop for Texture {
 }
 
 pub fn create(data: &[u8]) -> Result<Texture, String> {
-    let image = image::load_from_memory(data).map_err(error_to_string())?;
+    let image = image::load_from_memory(data)
+        .map_err(error_to_string())?
+        .to_rgba8();
+    upload(image.width(), image.height(), image.as_raw())
+}
+
+pub fn from_colour(colour: [f32; 3]) -> Result<Texture, String> {
+    let [r, g, b] = colour.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    upload(1, 1, &[r, g, b, 255])
+}
 
+fn upload(width: u32, height: u32, rgba: &[u8]) -> Result<Texture, String> {
     let mut id: GLuint = 0;
     unsafe {
         gl::GenTextures(1, &mut id);
@@ -43,12 +53,12 @@ pub fn create(data: &[u8]) -> Result<Texture, String> {
             gl::TEXTURE_2D,
             0,
             gl::RGBA as i32,
-            image.width().try_into().map_err(error_to_string())?,
-            image.height().try_into().map_err(error_to_string())?,
+            width.try_into().map_err(error_to_string())?,
+            height.try_into().map_err(error_to_string())?,
             0,
             gl::RGBA,
             gl::UNSIGNED_BYTE,
-            image.as_bytes().as_ptr() as *const std::os::raw::c_void,
+            rgba.as_ptr() as *const std::os::raw::c_void,
         );
         gl::GenerateMipmap(gl::TEXTURE_2D);
     }
@@ -0,0 +1,67 @@
+#version 330 core
+
+#define OPERATOR_REINHARD 0
+#define OPERATOR_ACES 1
+#define OPERATOR_UNCHARTED2 2
+
+uniform sampler2D uImage;
+uniform float uExposure;
+uniform int uOperator;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+vec3 reinhard(vec3 colour);
+vec3 aces(vec3 colour);
+vec3 uncharted2(vec3 colour);
+
+void main()
+{
+    vec3 colour = texture(uImage, aTextureCoords).rgb * uExposure;
+
+    vec3 mapped;
+    if (uOperator == OPERATOR_ACES) {
+        mapped = aces(colour);
+    } else if (uOperator == OPERATOR_UNCHARTED2) {
+        mapped = uncharted2(colour);
+    } else {
+        mapped = reinhard(colour);
+    }
+
+    aFragColours = vec4(clamp(mapped, 0.0, 1.0), 1.0);
+}
+
+vec3 reinhard(vec3 colour)
+{
+    return colour / (colour + vec3(1.0));
+}
+
+vec3 aces(vec3 colour)
+{
+    // Narkowicz's fit of the ACES filmic curve
+    float a = 2.51;
+    float b = 0.03;
+    float c = 2.43;
+    float d = 0.59;
+    float e = 0.14;
+    return (colour * (a * colour + b)) / (colour * (c * colour + d) + e);
+}
+
+vec3 uncharted2_curve(vec3 x)
+{
+    float a = 0.15;
+    float b = 0.50;
+    float c = 0.10;
+    float d = 0.20;
+    float e = 0.02;
+    float f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+vec3 uncharted2(vec3 colour)
+{
+    float exposure_bias = 2.0;
+    vec3 white_point = vec3(11.2);
+    return uncharted2_curve(colour * exposure_bias) / uncharted2_curve(white_point);
+}
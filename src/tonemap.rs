@@ -0,0 +1,95 @@
+use crate::postprocess::{Pass, Uniform};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Operator {
+    Reinhard,
+    Aces,
+    Uncharted2,
+}
+
+impl Operator {
+    fn next(&self) -> Operator {
+        match self {
+            Operator::Reinhard => Operator::Aces,
+            Operator::Aces => Operator::Uncharted2,
+            Operator::Uncharted2 => Operator::Reinhard,
+        }
+    }
+
+    fn shader_index(&self) -> i32 {
+        match self {
+            Operator::Reinhard => 0,
+            Operator::Aces => 1,
+            Operator::Uncharted2 => 2,
+        }
+    }
+}
+
+const MIN_EXPOSURE: f32 = 0.05;
+const MAX_EXPOSURE: f32 = 20.0;
+
+pub struct Settings {
+    operator: Operator,
+    exposure: f32,
+    auto_exposure: bool,
+    key: f32,
+    adaptation_speed: f32,
+}
+
+impl Settings {
+    pub fn is_auto_exposure(&self) -> bool {
+        self.auto_exposure
+    }
+
+    pub fn cycle_operator(&mut self) {
+        self.operator = self.operator.next();
+        println!("Tone mapping: {:?}", self.operator);
+    }
+
+    pub fn toggle_auto_exposure(&mut self) {
+        self.auto_exposure = !self.auto_exposure;
+        println!(
+            "Auto exposure: {}",
+            if self.auto_exposure { "on" } else { "off" }
+        );
+    }
+
+    pub fn scale_exposure(&mut self, factor: f32) {
+        self.auto_exposure = false;
+        self.exposure = num::clamp(self.exposure * factor, MIN_EXPOSURE, MAX_EXPOSURE);
+        println!("Exposure: {:.2}", self.exposure);
+    }
+
+    pub fn adapt(&mut self, average_colour: &nalgebra_glm::Vec3, delta_seconds: f32) {
+        if !self.auto_exposure {
+            return;
+        }
+
+        let target = num::clamp(
+            self.key / luminance(average_colour).max(0.0001),
+            MIN_EXPOSURE,
+            MAX_EXPOSURE,
+        );
+        let blend = 1.0 - (-delta_seconds * self.adaptation_speed).exp();
+        self.exposure += (target - self.exposure) * blend;
+    }
+
+    pub fn apply(&self, pass: &mut Pass) {
+        pass.set_uniform("uExposure", Uniform::Float(self.exposure));
+        pass.set_uniform("uOperator", Uniform::Int(self.operator.shader_index()));
+    }
+}
+
+pub fn create(operator: Operator, exposure: f32) -> Settings {
+    Settings {
+        operator,
+        exposure,
+        auto_exposure: false,
+        key: 0.18,
+        adaptation_speed: 1.5,
+    }
+}
+
+fn luminance(colour: &nalgebra_glm::Vec3) -> f32 {
+    nalgebra_glm::dot(colour, &nalgebra_glm::vec3(0.2126, 0.7152, 0.0722))
+}
@@ -0,0 +1,18 @@
+#version 330 core
+
+uniform sampler2D uImage;
+uniform float uRadius;
+uniform float uSoftness;
+uniform float uStrength;
+
+in vec2 aTextureCoords;
+
+out vec4 aFragColours;
+
+void main()
+{
+    vec3 colour = texture(uImage, aTextureCoords).rgb;
+    float distance = length(aTextureCoords - vec2(0.5));
+    float vignette = smoothstep(uRadius, uRadius - uSoftness, distance);
+    aFragColours = vec4(colour * mix(1.0, vignette, uStrength), 1.0);
+}